@@ -1,14 +1,13 @@
 use ggez::{
     graphics::{Color as ggezColor, DrawParam},
-    timer::{duration_to_f64, get_delta},
-    Context,
+    timer::duration_to_f64,
 };
 use std::{f32::consts::PI, time::Duration};
 
 use color::Color;
 use update::Updatable;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum AnimationType {
     PulseScale {
         amplitude: f32,
@@ -20,7 +19,7 @@ pub enum AnimationType {
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Animation {
     time_elapsed: Duration,
     animation_duration: Duration,
@@ -100,7 +99,7 @@ impl Animation {
 impl<'a> Updatable<'a> for Animation {
     type Data = ();
 
-    fn update(&'a mut self, ctx: &'a Context, _data: ()) {
-        self.time_elapsed += get_delta(ctx)
+    fn update(&'a mut self, delta: Duration, _data: ()) {
+        self.time_elapsed += delta
     }
 }
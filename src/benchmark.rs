@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use config::{Difficulty, DifficultySettings, GameMode};
+use draw::Drawable;
+use map::generator::{generate, GeneratorSettings};
+use simulation::Simulation;
+
+/// Runs the given function a number of times, returning the average time per run.
+fn time<F: FnMut()>(runs: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    start.elapsed() / runs
+}
+
+/// Benchmarks world lookups and path finding on a large generated map,
+/// printing the results. Runs without a window.
+pub fn run(width: u32, height: u32, seed: u64) {
+    let settings = GeneratorSettings {
+        width,
+        height,
+        closed_ports: 30,
+        ..Default::default()
+    };
+    let generation_start = Instant::now();
+    let map = generate(&settings, seed).expect("Failed to generate benchmark map");
+    let mut simulation = Simulation::from_map(
+        map,
+        Difficulty::Normal,
+        DifficultySettings::default(),
+        GameMode::default(),
+        seed,
+    )
+    .expect("Failed to create benchmark world");
+    let world = simulation.world().clone();
+    println!(
+        "Generated {}x{} map (seed {}) in {:?}",
+        width,
+        height,
+        seed,
+        generation_start.elapsed()
+    );
+
+    // Neighbor lookups as done when drawing the map each frame.
+    // Linear search is only timed for a single row, as a full frame takes minutes.
+    let mut found = 0;
+    let linear = time(1, || {
+        for tile in world.tiles().iter().take(width as usize) {
+            for neighbor in tile.neighbors().iter() {
+                if world.tiles().iter().any(|t| t.position() == *neighbor) {
+                    found += 1;
+                }
+            }
+        }
+    }) * height;
+    let indexed = time(10, || {
+        for tile in world.tiles() {
+            for neighbor in tile.neighbors().iter() {
+                if world.tile(*neighbor).is_some() {
+                    found += 1;
+                }
+            }
+        }
+    });
+    println!(
+        "Neighbor lookups per frame ({} found): linear {:?} (estimated), indexed {:?}",
+        found, linear, indexed
+    );
+
+    let drawing = time(10, || {
+        for tile in world.tiles() {
+            tile.draw(&world);
+        }
+    });
+    println!("Drawing all tiles: {:?}", drawing);
+
+    let mut found = 0;
+    let port_lookups = time(10, || {
+        for tile in world.tiles() {
+            if world.port(tile.position()).is_some() {
+                found += 1;
+            }
+        }
+    });
+    println!(
+        "Port lookups of all tiles ({} found): {:?}",
+        found, port_lookups
+    );
+
+    // Path previews between the open ports, as updated on mouse movement.
+    let ports = world
+        .ports()
+        .iter()
+        .map(|port| port.position())
+        .collect::<Vec<_>>();
+    let path_finding = time(10, || {
+        for (start, goal) in ports.iter().zip(ports.iter().skip(1)) {
+            world.route(*start, *goal);
+        }
+    });
+    println!(
+        "Path finding between {} open ports: {:?}",
+        ports.len(),
+        path_finding
+    );
+
+    // Full session without any routes, stepped as by the game loop.
+    // Ports overloading do not end the session, so that the full length is timed.
+    let session_length = simulation
+        .game_timer()
+        .time_remaining()
+        .expect("Benchmark session is not timed");
+    let step = Duration::from_secs(1) / 60;
+    let ports_before = world.ports().len();
+    let simulation_start = Instant::now();
+    while !simulation.game_timer().has_game_ended() {
+        simulation.tick(step);
+    }
+    println!(
+        "Simulated {:?} session in {:?}, open ports {} -> {}",
+        session_length,
+        simulation_start.elapsed(),
+        ports_before,
+        simulation.world().ports().len()
+    );
+}
@@ -1,23 +1,63 @@
-/// A drawable color of the game's color scheme.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Color {
-    Blue,
-    Green,
-    Red,
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use resource;
+
+/// A tradable good of the palette, given by its name and color.
+#[derive(Deserialize, Debug)]
+struct Good {
+    name: String,
+    rgb: (u8, u8, u8),
 }
 
+lazy_static! {
+    /// All goods which can be traded, in the order they are added. Read
+    /// from the resources folder once, when a color is first used.
+    static ref PALETTE: Vec<Good> = resource::load_list("palette.json");
+}
+
+/// A drawable color of the game's color scheme, one for each good of the
+/// palette. Stored by name, so that maps and saves read well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color(usize);
+
 impl Color {
+    /// Returns the color of the good with the given name, if any.
+    pub fn from_name(name: &str) -> Option<Color> {
+        PALETTE.iter().position(|good| good.name == name).map(Color)
+    }
+
+    /// Returns the name of the good.
+    pub fn name(self) -> &'static str {
+        &PALETTE[self.0].name
+    }
+
     /// Return a tuple of the color's components in the RGB scheme.
     pub fn rgb(self) -> (u8, u8, u8) {
-        match self {
-            Color::Blue => (46, 83, 161),
-            Color::Green => (2, 170, 92),
-            Color::Red => (232, 66, 54),
-        }
+        PALETTE[self.0].rgb
     }
 
-    /// Returns all color variants.
+    /// Returns all colors of the palette.
     pub fn values() -> Vec<Color> {
-        vec![Color::Blue, Color::Green, Color::Red]
+        (0..PALETTE.len()).map(Color).collect()
+    }
+}
+
+impl Default for Color {
+    /// Returns the first color of the palette.
+    fn default() -> Color {
+        Color(0)
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Color::from_name(&name).ok_or_else(|| de::Error::custom(format!("unknown color: {}", name)))
     }
 }
@@ -1,9 +1,241 @@
+use std::time::Duration;
+
+use ship::ShipClass;
+
+/// Score to reach in target score mode, unless given.
+const DEFAULT_TARGET_SCORE: u32 = 10;
+
+/// How hard a game session is to play, as one of the presets or custom
+/// settings.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Custom,
+}
+
+impl Difficulty {
+    /// Returns the name of the difficulty, as given on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Custom => "custom",
+        }
+    }
+
+    /// Returns the difficulty as shown in menus.
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Custom => "Custom",
+        }
+    }
+
+    /// Returns the settings of the preset, or None if custom.
+    pub fn preset(self) -> Option<DifficultySettings> {
+        match self {
+            Difficulty::Easy => Some(DifficultySettings {
+                port_interval: 20,
+                initial_ships: 4,
+                ship_speed: 120,
+                docking_time: 75,
+                colors: 2,
+            }),
+            Difficulty::Normal => Some(DifficultySettings {
+                port_interval: 15,
+                initial_ships: 3,
+                ship_speed: 100,
+                docking_time: 100,
+                colors: 3,
+            }),
+            Difficulty::Hard => Some(DifficultySettings {
+                port_interval: 10,
+                initial_ships: 2,
+                ship_speed: 90,
+                docking_time: 125,
+                colors: 4,
+            }),
+            Difficulty::Custom => None,
+        }
+    }
+
+    /// Returns all difficulty variants.
+    pub fn values() -> Vec<Difficulty> {
+        vec![
+            Difficulty::Easy,
+            Difficulty::Normal,
+            Difficulty::Hard,
+            Difficulty::Custom,
+        ]
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Difficulty {
+        Difficulty::Normal
+    }
+}
+
+/// Pacing of a game session as given by its difficulty.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DifficultySettings {
+    /// Seconds between the regular progression steps, such as opening ports.
+    pub port_interval: u64,
+    /// Ships available in the shipyard at the start of a session.
+    pub initial_ships: usize,
+    /// Speed of ships, in percent of the speed of their class.
+    pub ship_speed: u32,
+    /// Time ships spend docked, in percent of the docking time of their class.
+    pub docking_time: u32,
+    /// Number of colors traded from the start, at least two.
+    pub colors: usize,
+}
+
+impl DifficultySettings {
+    /// Returns the speed in tiles per second of ships of the given class.
+    pub fn speed(&self, class: ShipClass) -> f32 {
+        class.speed() * self.ship_speed as f32 / 100.
+    }
+
+    /// Returns the time ships of the given class spend docked at each port.
+    pub fn docking_time(&self, class: ShipClass) -> Duration {
+        class.docking_time() * self.docking_time / 100
+    }
+}
+
+impl Default for DifficultySettings {
+    fn default() -> DifficultySettings {
+        Difficulty::Normal
+            .preset()
+            .expect("Normal difficulty is a preset")
+    }
+}
+
+/// How a game session ends and is scored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// Ends after the given number of seconds, or the session length of the
+    /// map if not given, or when a port overloads.
+    Timed(Option<u64>),
+    /// Ends only when a port overloads.
+    Endless,
+    /// Ends when the given score is reached, as fast as possible, or when a
+    /// port overloads.
+    TargetScore(u32),
+    /// Never ends, ports do not overload.
+    Zen,
+}
+
+impl GameMode {
+    /// Returns the name of the mode, as given on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Timed(_) => "timed",
+            GameMode::Endless => "endless",
+            GameMode::TargetScore(_) => "target",
+            GameMode::Zen => "zen",
+        }
+    }
+
+    /// Returns the mode as shown in menus.
+    pub fn label(self) -> String {
+        match self {
+            GameMode::Timed(Some(length)) => format!("Timed {}:{:02}", length / 60, length % 60),
+            GameMode::Timed(None) => "Timed".to_owned(),
+            GameMode::Endless => "Endless".to_owned(),
+            GameMode::TargetScore(target) => format!("Target {}", target),
+            GameMode::Zen => "Zen".to_owned(),
+        }
+    }
+
+    /// Returns the mode with the given name, using the given parameter
+    /// (if any) as the session length or target score.
+    pub fn from_name(name: &str, parameter: Option<u32>) -> Option<GameMode> {
+        match name {
+            "timed" => Some(GameMode::Timed(parameter.map(u64::from))),
+            "endless" => Some(GameMode::Endless),
+            "target" => Some(GameMode::TargetScore(
+                parameter.unwrap_or(DEFAULT_TARGET_SCORE),
+            )),
+            "zen" => Some(GameMode::Zen),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the mode ranks results by the time taken rather than
+    /// by score.
+    pub fn is_ranked_by_time(self) -> bool {
+        match self {
+            GameMode::TargetScore(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the text describing a result of the mode, given its score,
+    /// the seconds played and whether the goal of the mode was completed.
+    pub fn result_text(self, score: u32, seconds: u64, is_completed: bool) -> String {
+        match self {
+            GameMode::Timed(_) | GameMode::Zen => format!("Score: {}", score),
+            GameMode::Endless => format!(
+                "Score: {}\nLasted {}:{:02}",
+                score,
+                seconds / 60,
+                seconds % 60
+            ),
+            GameMode::TargetScore(target) if is_completed => {
+                format!("Reached {} in {}:{:02}", target, seconds / 60, seconds % 60)
+            }
+            GameMode::TargetScore(target) => format!(
+                "Score: {} of {}\nLost after {}:{:02}",
+                score,
+                target,
+                seconds / 60,
+                seconds % 60
+            ),
+        }
+    }
+
+    /// Returns all modes with their default parameters.
+    pub fn values() -> Vec<GameMode> {
+        vec![
+            GameMode::Timed(None),
+            GameMode::Endless,
+            GameMode::TargetScore(DEFAULT_TARGET_SCORE),
+            GameMode::Zen,
+        ]
+    }
+}
+
+impl Default for GameMode {
+    fn default() -> GameMode {
+        GameMode::Timed(None)
+    }
+}
+
 /// Holds game specific configurations.
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
     pub scaling: u32, // Scaling needs to be handled due to Apples OpenGL implementation.
     pub grid_width: u32, // Width of the grid.
     pub grid_height: u32, // Height of the grid.
+    pub map_seed: Option<u64>, // Seed of the generated map, if not using the default map.
+    pub seed: Option<u64>, // Seed of the game session, random if not given.
+    pub difficulty: Difficulty, // Difficulty of the game session.
+    pub custom_difficulty: DifficultySettings, // Settings used by the custom difficulty.
+    pub game_mode: GameMode, // How the game session ends and is scored.
+}
+
+impl Config {
+    /// Returns the settings of the chosen difficulty.
+    pub fn difficulty_settings(&self) -> DifficultySettings {
+        self.difficulty.preset().unwrap_or(self.custom_difficulty)
+    }
 }
 
 impl Default for Config {
@@ -12,6 +244,11 @@ impl Default for Config {
             scaling: 1,
             grid_width: 60,
             grid_height: 30,
+            map_seed: None,
+            seed: None,
+            difficulty: Difficulty::Normal,
+            custom_difficulty: DifficultySettings::default(),
+            game_mode: GameMode::default(),
         }
     }
 }
@@ -9,6 +9,9 @@ use ggez::{
 use animation::Animation;
 use config::Config;
 
+/// Path of the image holding all sprites.
+pub const TILESET_PATH: &str = "/tileset.png";
+
 /// A drawable type.
 pub trait Drawable<'a> {
     // TODO: Move magic constant here.
@@ -0,0 +1,377 @@
+use ggez::{
+    event::{self, Keycode, Mod},
+    graphics::{self, Color as ggezColor, DrawParam},
+    mouse, {Context, GameResult},
+};
+use std::iter;
+
+use audio::{AudioHandler, SoundEffect};
+use color::Color;
+use config::Config;
+use draw::{self, Drawable, SpriteDrawer};
+use fonts::FontCache;
+use geometry::Position;
+use map::{self, MapData, PortDefinition};
+use port::Port;
+use scene::{Scene, Transition};
+use tile::{Tile, TileKind};
+use world::World;
+
+/// Path in the user directory where edited maps are saved.
+const EDITOR_MAP_PATH: &str = "/editor-map.json";
+
+/// The kind of edit applied when clicking on the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditorTool {
+    Land,
+    Water,
+    OpenPort,
+    ClosedPort,
+}
+
+/// Returns the color after the given one, cycling through unset (sampled
+/// when the map is loaded) after the last color.
+fn next_color(color: Option<Color>) -> Option<Color> {
+    let colors = Color::values();
+    match color {
+        None => colors.first().cloned(),
+        Some(color) => colors.iter().skip_while(|c| **c != color).nth(1).cloned(),
+    }
+}
+
+/// A port definition as drawn in the editor.
+/// Unset colors are drawn black and closed ports are drawn faded.
+struct EditorPort<'a>(&'a PortDefinition);
+
+impl<'a, 'b> Drawable<'b> for EditorPort<'a> {
+    type Data = World;
+
+    fn draw(&self, world: &World) -> Vec<DrawParam> {
+        let definition = self.0;
+        let port = Port::new(
+            definition.position,
+            definition.import.unwrap_or_default(),
+            definition.export.unwrap_or_default(),
+        );
+        let alpha = if definition.is_initially_open() {
+            255
+        } else {
+            100
+        };
+
+        // Port draws the import color first, then the export color.
+        port.draw(world)
+            .into_iter()
+            .zip(&[definition.import, definition.export])
+            .map(|(param, color)| {
+                let (r, g, b) = color.map_or((69, 55, 52), |c| c.rgb());
+                DrawParam {
+                    color: Some(ggezColor::from_rgba(r, g, b, alpha)),
+                    ..param
+                }
+            }).collect()
+    }
+}
+
+/// Handles editing, validation and saving of maps.
+pub struct MapEditorState {
+    font_cache: FontCache,
+    audio_handler: AudioHandler,
+    config: Config,
+    sprite_drawer: SpriteDrawer,
+    map: MapData,
+    /// Tiles of the map, kept as a world for autotiling when drawing.
+    world: World,
+    tool: EditorTool,
+    is_painting: bool,
+    status: String,
+    is_leaving: bool,
+}
+
+impl MapEditorState {
+    /// Creates a new map editor state, opening the last saved map if any.
+    pub fn new(ctx: &mut Context, mut config: Config) -> GameResult<Self> {
+        let map = if ctx.filesystem.is_file(EDITOR_MAP_PATH) {
+            map::load(ctx, EDITOR_MAP_PATH)?
+        } else {
+            map::load(ctx, map::DEFAULT_MAP_PATH)?
+        };
+        config.grid_width = map.width;
+        config.grid_height = map.height;
+
+        // Load spritebatch for effective drawing of sprites.
+        let image = graphics::Image::new(ctx, draw::TILESET_PATH)?;
+        let sprite_drawer = SpriteDrawer::new(image);
+
+        let world = World::new(map.tiles.iter().cloned(), iter::empty(), iter::empty());
+        let state = MapEditorState {
+            font_cache: FontCache::new(ctx),
+            audio_handler: AudioHandler::new(ctx),
+            config,
+            sprite_drawer,
+            map,
+            world,
+            tool: EditorTool::Land,
+            is_painting: false,
+            status: String::new(),
+            is_leaving: false,
+        };
+        Ok(state)
+    }
+
+    /// Converts a mouse position to a grid position.
+    fn grid_position(&self, ctx: &Context, x: f32, y: f32) -> Position {
+        let (window_width, _) = graphics::get_drawable_size(ctx);
+        let cell_size = (self.config.scaling * window_width) as f32 / self.config.grid_width as f32;
+        Position::new(
+            (self.config.scaling as f32 * x / cell_size) as i32,
+            (self.config.scaling as f32 * y / cell_size) as i32,
+        )
+    }
+
+    /// Returns the grid position currently under the mouse.
+    fn mouse_grid_position(&self, ctx: &Context) -> Option<Position> {
+        mouse::get_position(ctx)
+            .ok()
+            .map(|p| self.grid_position(ctx, p.coords.x, p.coords.y))
+    }
+
+    /// Sets the kind of the tile at the given position, removing any port
+    /// if it is turned into water.
+    fn set_tile(&mut self, position: Position, kind: TileKind) {
+        let changed = match self
+            .map
+            .tiles
+            .iter_mut()
+            .find(|tile| tile.position() == position)
+        {
+            Some(tile) if tile.kind() != kind => {
+                *tile = Tile::new(position, kind);
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            if kind == TileKind::Water {
+                self.map.ports.retain(|port| port.position != position);
+            }
+            self.world = World::new(self.map.tiles.iter().cloned(), iter::empty(), iter::empty());
+        }
+    }
+
+    /// Places an open or closed port at the given position if it is land,
+    /// replacing any existing port.
+    fn place_port(&mut self, position: Position, open: bool) {
+        let is_land = self
+            .world
+            .tile(position)
+            .map_or(false, |tile| tile.kind() == TileKind::Land);
+        if !is_land {
+            return;
+        }
+        let open_time = if open { Some(0) } else { None };
+
+        if let Some(port) = self.port_mut(position) {
+            port.open_time = open_time;
+            return;
+        }
+
+        // Find the first unused port number.
+        let name = (1..)
+            .map(|i| format!("Port {}", i))
+            .find(|name| !self.map.ports.iter().any(|p| p.name == *name))
+            .unwrap();
+        self.map.ports.push(PortDefinition {
+            name,
+            position,
+            import: None,
+            export: None,
+            open_time,
+        });
+    }
+
+    /// Removes the port at the given position, if any.
+    fn remove_port(&mut self, position: Position) {
+        self.map.ports.retain(|port| port.position != position);
+    }
+
+    /// Returns a mutable reference to the port at the given position.
+    fn port_mut(&mut self, position: Position) -> Option<&mut PortDefinition> {
+        self.map
+            .ports
+            .iter_mut()
+            .find(|port| port.position == position)
+    }
+
+    /// Applies the current tool at the given position.
+    fn apply_tool(&mut self, position: Position) {
+        match self.tool {
+            EditorTool::Land => self.set_tile(position, TileKind::Land),
+            EditorTool::Water => self.set_tile(position, TileKind::Water),
+            EditorTool::OpenPort => self.place_port(position, true),
+            EditorTool::ClosedPort => self.place_port(position, false),
+        }
+    }
+
+    /// Checks that the map is well formed and all ports can reach each other.
+    fn validate(&mut self) {
+        self.status = match self
+            .map
+            .validate()
+            .and_then(|_| self.map.check_connectivity())
+        {
+            Ok(_) => "Map is valid".to_owned(),
+            Err(err) => err.to_string(),
+        };
+    }
+
+    /// Saves the map to the user directory.
+    fn save(&mut self, ctx: &mut Context) {
+        self.status = match ctx.filesystem.create(EDITOR_MAP_PATH) {
+            Ok(file) => match self.map.write(file) {
+                Ok(_) => format!("Saved to {}", EDITOR_MAP_PATH),
+                Err(err) => err.to_string(),
+            },
+            Err(err) => format!("Failed to save map: {}", err),
+        };
+    }
+}
+
+impl Scene for MapEditorState {
+    /// Updates the editor state, returning to the menu when leaving.
+    fn update(&mut self, _ctx: &mut Context) -> GameResult<Transition> {
+        if self.is_leaving {
+            Ok(Transition::Pop)
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    /// Handle mouse down events, applying the current tool or removing ports.
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: event::MouseButton,
+        x: i32,
+        y: i32,
+    ) {
+        let position = self.grid_position(ctx, x as f32, y as f32);
+        match button {
+            event::MouseButton::Right => self.remove_port(position),
+            _ => {
+                self.is_painting = true;
+                self.apply_tool(position);
+            }
+        }
+    }
+
+    /// Handle mouse up events, stopping painting.
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: event::MouseButton,
+        _x: i32,
+        _y: i32,
+    ) {
+        self.is_painting = false;
+    }
+
+    /// Handle mouse movement events, painting tiles while the mouse is held.
+    fn mouse_motion_event(
+        &mut self,
+        ctx: &mut Context,
+        _button: event::MouseState,
+        x: i32,
+        y: i32,
+        _xrel: i32,
+        _yrel: i32,
+    ) {
+        let is_tile_tool = self.tool == EditorTool::Land || self.tool == EditorTool::Water;
+        if self.is_painting && is_tile_tool {
+            let position = self.grid_position(ctx, x as f32, y as f32);
+            self.apply_tool(position);
+        }
+    }
+
+    /// Handle key presses: tool selection, port colors, validation and saving.
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, repeat: bool) {
+        if repeat {
+            return;
+        }
+        match keycode {
+            Keycode::Num1 => self.tool = EditorTool::Land,
+            Keycode::Num2 => self.tool = EditorTool::Water,
+            Keycode::Num3 => self.tool = EditorTool::OpenPort,
+            Keycode::Num4 => self.tool = EditorTool::ClosedPort,
+            Keycode::I | Keycode::E => {
+                if let Some(position) = self.mouse_grid_position(ctx) {
+                    if let Some(port) = self.port_mut(position) {
+                        if keycode == Keycode::I {
+                            port.import = next_color(port.import);
+                        } else {
+                            port.export = next_color(port.export);
+                        }
+                    }
+                }
+            }
+            Keycode::V => {
+                self.audio_handler.play(SoundEffect::ClickUIButton);
+                self.validate();
+            }
+            Keycode::S => {
+                self.audio_handler.play(SoundEffect::ClickUIButton);
+                self.save(ctx);
+            }
+            Keycode::Escape => self.is_leaving = true,
+            _ => (),
+        }
+    }
+
+    /// Draws the current state to the screen with the given context.
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.sprite_drawer.clear();
+
+        // Draw all base tiles.
+        for tile in self.world.tiles() {
+            self.sprite_drawer
+                .draw_item(ctx, &self.config, tile, &self.world, true);
+        }
+
+        // Draw all ports.
+        for port in &self.map.ports {
+            self.sprite_drawer
+                .draw_item(ctx, &self.config, &EditorPort(port), &self.world, true);
+        }
+
+        // Draw to screen.
+        self.sprite_drawer.paint(ctx, &self.config)?;
+
+        // Draw tool and status line below the map.
+        let (window_width, _) = graphics::get_drawable_size(ctx);
+        let cell_size = (self.config.scaling * window_width) as f32 / self.config.grid_width as f32;
+        let help = format!(
+            "{:?} [1-4: tool, I/E: port colors, right click: remove port, V: validate, S: save]",
+            self.tool
+        );
+        graphics::set_color(ctx, ggezColor::from_rgb(69, 55, 52))?;
+        for (i, line) in [help.as_str(), self.status.as_str()].iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let text = graphics::Text::new(ctx, line, self.font_cache.small())?;
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::Point2::new(
+                    cell_size,
+                    (self.config.grid_height as f32 + 0.5 + 1.2 * i as f32) * cell_size,
+                ),
+                0.,
+            )?;
+        }
+        // Reset color to default (white).
+        graphics::set_color(ctx, ggezColor::from_rgb(255, 255, 255))?;
+
+        Ok(())
+    }
+}
@@ -1,314 +1,310 @@
 use ggez::{
-    event, graphics, mouse, timer, {Context, GameResult},
+    event::{self, Keycode, Mod},
+    graphics, mouse, timer, {Context, GameResult},
 };
-use rand::prelude::*;
-use rand::{Rng, ThreadRng};
+use rand;
 use std::{
-    cell::RefCell,
-    io::{BufRead, BufReader, Read},
+    cell::{Cell, RefCell},
     mem,
+    rc::Rc,
     time::Duration,
 };
 
 use animation::{Animation, AnimationType};
 use audio::{AudioHandler, SoundEffect};
-use color::Color;
 use config::Config;
-use draw::{Drawable, SpriteDrawer};
+use draw::{self, SpriteDrawer};
 use fonts::FontCache;
 use geometry::Position;
-use port::{is_valid_arrangement, Port};
+use highscore::HighScoreEntry;
+use history::EditHistory;
+use map::{self, generator::GeneratorSettings};
+use notification::Notification;
+use pausestate::{PauseChoice, PauseState};
 use route::{RouteBuilder, ShapeSelector, Waypoint};
+use save;
+use scene::{Scene, Transition};
+use scorestate::ScoreState;
 use ship::ShipBuilder;
-use tally::Tally;
-use tile::{Tile, TileKind};
-use time::GameTimer;
+use simulation::{EndReason, Simulation, SimulationEvent};
 use update::Updatable;
-use world::World;
-
-const TILESET_PATH: &str = "/tileset.png";
-const MAP_PATH: &str = "/map.ppm";
-
-/// Load world from image file, mapping RGB to tiles.
-fn load_world<R: Rng>(ctx: &mut Context, config: &Config, color_sampler: &mut R) -> World {
-    let mut map_file = ctx.filesystem.open(MAP_PATH).unwrap();
-    let mut header_buffer = [0; 58];
-    map_file.read_exact(&mut header_buffer).unwrap();
-
-    let map_file = BufReader::new(map_file);
-    let buffer: Vec<i32> = map_file
-        .lines()
-        .map(|s| s.unwrap().parse::<i32>().unwrap())
-        .collect();
-
-    let mut map = vec![];
-    let mut open_ports = vec![];
-    let mut closed_ports = vec![];
-
-    // Generate order of import, export colors for inital ports.
-    let (mut imports, mut exports) = (Color::values(), Color::values());
-    loop {
-        color_sampler.shuffle(&mut imports);
-        color_sampler.shuffle(&mut exports);
-
-        // Reshuffle until valid import/export combinations
-        if imports.iter().zip(exports.iter()).all(|(i, e)| i != e) {
-            break;
-        }
-    }
 
-    buffer
-        .as_slice()
-        .chunks(3)
-        .enumerate()
-        .for_each(|(i, colors)| {
-            let index = i as i32;
-            let position = Position::new(
-                index % config.grid_width as i32,
-                index / config.grid_width as i32,
-            );
-            match colors {
-                [0, 0, 255] => map.push(Tile::new(position, TileKind::Water)),
-                [255, 0, 0] => {
-                    let (import, export) = Port::sample_colors(color_sampler);
-                    closed_ports.push(Port::new(position, import, export));
-                    map.push(Tile::new(position, TileKind::Land));
-                }
-                [0, 255, 0] => {
-                    let import = imports.pop().expect("More open ports than expected");
-                    let export = exports.pop().expect("More open ports than expected");
-                    open_ports.push(Port::new(position, import, export));
-                    map.push(Tile::new(position, TileKind::Land));
-                }
-                _ => map.push(Tile::new(position, TileKind::Land)),
-            }
-        });
-    World::new(
-        map.into_iter(),
-        open_ports.into_iter(),
-        closed_ports.into_iter(),
-    )
-}
+/// Number of fixed simulation steps per second.
+const UPDATES_PER_SECOND: u32 = 60;
 
-/// Time of a single game session: 5min.
-static GAME_TIME_LENGTH: u64 = 60 * 5;
+/// Discards game time accumulated while the game was not updated, such as
+/// when in the menu or paused, so that it is not caught up on.
+fn skip_update_time(ctx: &mut Context) {
+    while timer::check_update_time(ctx, UPDATES_PER_SECOND) {}
+}
 
 /// Handles and holds all game information.
-pub struct GameState<'a> {
+pub struct GameState {
     font_cache: FontCache,
     audio_handler: AudioHandler,
     config: Config,
-    frames: usize,
     sprite_drawer: SpriteDrawer,
-    world: World,
+    simulation: Simulation,
     route_builder: Option<RouteBuilder>,
     ship_builder: Option<ShipBuilder>,
     shape_selector: ShapeSelector,
-    rng: ThreadRng,
-    game_timer: GameTimer,
-    last_progression_step: Duration,
-    tally: &'a RefCell<Tally>,
+    history: EditHistory,
+    notification: Option<Notification>,
     is_game_over: bool,
+    is_quitting: bool,
+    is_pausing: bool,
+    is_restarting: bool,
+    /// Choice made in the pause menu, set when the pause overlay is left.
+    pause_choice: Rc<Cell<Option<PauseChoice>>>,
+    /// Notice shown by the menu when returning to it, such as a failure to
+    /// save the session.
+    notice: Rc<RefCell<Option<String>>>,
 }
 
-impl<'a> GameState<'a> {
-    /// Creates a new game state in Play mode.
-    pub fn new(ctx: &mut Context, config: Config, tally: &'a RefCell<Tally>) -> GameResult<Self> {
-        let mut rng = thread_rng();
-        // Load game world from file.
-        let world = load_world(ctx, &config, &mut rng);
+impl GameState {
+    /// Creates a new game state in Play mode, leaving notices for the menu
+    /// in the given cell.
+    pub fn new(
+        ctx: &mut Context,
+        mut config: Config,
+        notice: Rc<RefCell<Option<String>>>,
+    ) -> GameResult<Self> {
+        // A single seed gives all randomness, making sessions reproducible.
+        let seed = config.seed.unwrap_or_else(rand::random);
+        config.seed = Some(seed);
+
+        // Generate or load game world from file, grid size is given by the map.
+        let map = match config.map_seed {
+            Some(seed) => {
+                let settings = GeneratorSettings {
+                    width: config.grid_width,
+                    height: config.grid_height,
+                    ..Default::default()
+                };
+                map::generator::generate(&settings, seed)?
+            }
+            None => map::load(ctx, map::DEFAULT_MAP_PATH)?,
+        };
+        config.grid_width = map.width;
+        config.grid_height = map.height;
+        let simulation = Simulation::from_map(
+            map,
+            config.difficulty,
+            config.difficulty_settings(),
+            config.game_mode,
+            seed,
+        )?;
+        GameState::with_simulation(ctx, config, simulation, notice)
+    }
+
+    /// Creates a game state resuming the given saved simulation, leaving
+    /// notices for the menu in the given cell.
+    pub fn resume(
+        ctx: &mut Context,
+        mut config: Config,
+        simulation: Simulation,
+        notice: Rc<RefCell<Option<String>>>,
+    ) -> GameResult<Self> {
+        config.grid_width = simulation.world().width();
+        config.grid_height = simulation.world().height();
+        config.seed = Some(simulation.seed());
+        config.difficulty = simulation.difficulty();
+        config.custom_difficulty = simulation.difficulty_settings();
+        config.game_mode = simulation.mode();
+        GameState::with_simulation(ctx, config, simulation, notice)
+    }
+
+    /// Creates a game state playing the given simulation.
+    fn with_simulation(
+        ctx: &mut Context,
+        config: Config,
+        simulation: Simulation,
+        notice: Rc<RefCell<Option<String>>>,
+    ) -> GameResult<Self> {
+        skip_update_time(ctx);
 
         // Load spritebatch for effective drawing of sprites.
-        let image = graphics::Image::new(ctx, TILESET_PATH)?;
+        let image = graphics::Image::new(ctx, draw::TILESET_PATH)?;
         let sprite_drawer = SpriteDrawer::new(image);
 
         let audio_handler = AudioHandler::new(ctx);
+        let shape_selector = ShapeSelector::new(simulation.world().lines().to_vec());
 
         let state = GameState {
             font_cache: FontCache::new(ctx),
             audio_handler,
             config,
-            frames: 0,
             sprite_drawer,
-            world,
+            simulation,
             route_builder: None,
             ship_builder: None,
-            shape_selector: ShapeSelector::new(),
-            rng: thread_rng(),
-            last_progression_step: timer::get_time_since_start(ctx),
-            game_timer: GameTimer::new(
-                timer::get_time_since_start(ctx),
-                Duration::from_secs(GAME_TIME_LENGTH),
-            ),
-            tally,
+            shape_selector,
+            history: EditHistory::new(),
+            notification: None,
             is_game_over: false,
+            is_quitting: false,
+            is_pausing: false,
+            is_restarting: false,
+            pause_choice: Rc::new(Cell::new(None)),
+            notice,
         };
         Ok(state)
     }
 
-    /// Updates the port colors by switching one port randomly until it is valid.
-    fn update_port_colors(&mut self) {
-        let ports = self.world.ports_mut();
-        loop {
-            // Extra brackes due to NLL not existing in stable Rust yet.
-            {
-                let port = self.rng.choose_mut(ports).unwrap();
-                let (import, export) = Port::sample_colors(&mut self.rng);
-
-                // Update if we got new colors.
-                if port.import() != import || port.export() != export {
-                    // Got valid colors
-                    *port.import_mut() = import;
-                    *port.export_mut() = export;
-
-                    // Add animation.
-                    *port.animation_mut() = Some(Animation::new(
-                        Duration::new(1, 0),
-                        AnimationType::PulseScale {
-                            amplitude: 0.4,
-                            rate: 1.,
-                        },
-                    ));
-                } else {
-                    continue;
-                }
+    /// Returns the result of the game session for the high-score table.
+    fn result(&self) -> HighScoreEntry {
+        HighScoreEntry::new(
+            self.simulation.tally().clone(),
+            self.simulation.map_title().to_owned(),
+            self.simulation.seed(),
+            self.simulation.difficulty(),
+            self.simulation.mode(),
+            self.simulation.game_timer().time_elapsed().as_secs(),
+            self.simulation.end_reason(),
+        )
+    }
+
+    /// Stops any route drawing or ship placement in progress, returning the
+    /// ship being placed to the shipyard.
+    fn cancel_builders(&mut self) {
+        if let Some(builder) = self.ship_builder.take() {
+            self.simulation
+                .world_mut()
+                .shipyard_mut()
+                .add_builder(builder);
+        }
+        if self.route_builder.take().is_some() {
+            for port in self.simulation.world_mut().ports_mut() {
+                *port.animation_mut() = None;
             }
-            if is_valid_arrangement(ports) {
-                break;
+        }
+    }
+
+    /// Adds a pulsing animation to the ports at the given positions.
+    fn pulse_ports(&mut self, positions: &[Position]) {
+        for port in self.simulation.world_mut().ports_mut() {
+            if positions.iter().any(|p| *p == port.position()) {
+                *port.animation_mut() = Some(Animation::new(
+                    Duration::new(3600, 0),
+                    AnimationType::PulseScale {
+                        amplitude: 0.1,
+                        rate: 1.,
+                    },
+                ));
             }
         }
     }
 
-    /// Ends the game session.
-    /// TODO: Currently only dumps the final score and quits.
+    /// Saves the game session so that it can be resumed later and returns
+    /// to the menu, which shows why if the session could not be saved.
+    fn quit_to_menu(&mut self, ctx: &mut Context) {
+        if let Err(err) = save::save(ctx, &mut self.simulation) {
+            *self.notice.borrow_mut() = Some(format!("Could not save game: {}", err));
+        }
+        self.is_quitting = true;
+    }
+
+    /// Ends the game session, as given by the game mode.
     fn end_game(&mut self, ctx: &mut Context) {
-        println!("GAME OVER: Score {}", self.tally.borrow().score());
+        match self.simulation.end_reason() {
+            Some(EndReason::Overload(position)) => {
+                let name = self
+                    .simulation
+                    .world()
+                    .port_name(position)
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| format!("Port at ({}, {})", position.x, position.y));
+                println!("{} was overloaded", name);
+            }
+            Some(EndReason::TargetReached) => println!(
+                "Target reached in {}s",
+                self.simulation.game_timer().time_elapsed().as_secs()
+            ),
+            Some(EndReason::TimeUp) | None => (),
+        }
+        println!("GAME OVER: Score {}", self.simulation.tally().score());
         self.is_game_over = true;
-        ctx.quit().expect("Failed to quit game");
+
+        // A finished session can not be resumed.
+        if let Err(err) = save::remove(ctx) {
+            println!("Failed to remove saved game: {}", err);
+        }
     }
 }
 
-impl<'a> event::EventHandler for GameState<'a> {
-    /// Updates the game state.
-    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // Check if game time is up, end game in such case.
-        if self.game_timer.has_game_ended(ctx) && !self.is_game_over {
+impl Scene for GameState {
+    /// Updates the game state, showing the score when the game has ended.
+    fn update(&mut self, ctx: &mut Context) -> GameResult<Transition> {
+        // Check if the game mode ends the session, end game in such case.
+        if self.simulation.is_over() && !self.is_game_over {
             self.end_game(ctx);
         }
+        if self.is_game_over {
+            let score_state = ScoreState::new(ctx, self.result())?;
+            return Ok(Transition::Replace(Box::new(score_state)));
+        }
+        // Return to menu, even if the session could not be saved.
+        if self.is_quitting {
+            return Ok(Transition::Pop);
+        }
+        if self.is_restarting {
+            let simulation = self.simulation.restart()?;
+            let game_state =
+                GameState::with_simulation(ctx, self.config, simulation, self.notice.clone())?;
+            return Ok(Transition::Replace(Box::new(game_state)));
+        }
+        if self.is_pausing {
+            self.is_pausing = false;
+            let pause_state = PauseState::new(ctx, self.config, self.pause_choice.clone())?;
+            return Ok(Transition::Push(Box::new(pause_state)));
+        }
 
-        while timer::check_update_time(ctx, 60) {
-            // Update all ships.
-            let tradings = self
-                .world
-                .ports()
-                .iter()
-                .map(|p| (p.position(), p.import(), p.export()))
-                .collect::<Vec<_>>();
-            let mut new_colors = vec![];
-
-            for (_, route) in self.world.routes_mut() {
-                let next_paths = route
-                    .ships()
-                    .map(|s| (s.reverse(), s.is_arriving(), s.next_waypoint().unwrap()))
-                    .map(|(reverse, is_arriving, curr)| {
-                        if is_arriving && !reverse {
-                            route.next_path(Position::from(curr))
-                        } else if is_arriving && reverse {
-                            route.previous_path(Position::from(curr))
-                        } else {
-                            None
-                        }
-                    }).collect::<Vec<_>>();
-                next_paths
-                    .into_iter()
-                    .zip(route.ships_mut())
-                    .for_each(|(path, ship)| {
-                        ship.update(ctx, path);
-                    });
-
-                for ship in route.ships_mut() {
-                    // Remove all animations that have finished.
-                    *ship.animation_mut() = match ship.animation_mut() {
-                        Some(ref mut animation) => {
-                            animation.update(ctx, ());
-                            if animation.has_finished() {
-                                None
-                            } else {
-                                Some(*animation)
-                            }
-                        }
-                        None => None,
-                    };
-
-                    if ship.is_docked() {
-                        let mut animation_length = 500;
-                        // TODO: Quick fix, We have no cargo, extend loading animation.
-                        if ship.cargo().is_none() {
-                            animation_length *= 2;
-                        }
-                        let (_, import, export) = tradings
-                            .iter()
-                            .find(|(p, _, _)| *p == Position::from(ship.position()))
-                            .expect("No port at ship dock");
-                        if let Some(cargo) = ship.try_unload() {
-                            // Unload ship cargo, adding to score if it matches port import.
-                            if cargo == *import {
-                                new_colors.push(cargo);
-                            }
-                            // TODO: Fix animation of throwing away cargo.
-                            if ship.animation().is_none() {
-                                *ship.animation_mut() = Some(Animation::new(
-                                    Duration::from_millis(animation_length),
-                                    AnimationType::ColorDrain {
-                                        from: Some(cargo),
-                                        to: None,
-                                    },
-                                ));
-                            }
-                        } else {
-                            ship.try_load(*export);
-                            // Add cargo loading animation if already not animated.
-                            if ship.animation().is_none() {
-                                *ship.animation_mut() = Some(Animation::new(
-                                    Duration::from_millis(animation_length),
-                                    AnimationType::ColorDrain {
-                                        from: None,
-                                        to: Some(*export),
-                                    },
-                                ));
-                            }
-                        }
+        // Advance the simulation in fixed steps.
+        let step = Duration::from_secs(1) / UPDATES_PER_SECOND;
+        while timer::check_update_time(ctx, UPDATES_PER_SECOND) {
+            for event in self.simulation.tick(step) {
+                match event {
+                    SimulationEvent::ProgressionStep => {
+                        self.audio_handler.play(SoundEffect::ProgressionStep)
+                    }
+                    SimulationEvent::ShipsGranted(ships) => {
+                        self.notification = Some(Notification::new(format!(
+                            "+{} ship{}",
+                            ships,
+                            if ships == 1 { "" } else { "s" }
+                        )));
+                    }
+                    SimulationEvent::LinesGranted(lines) => {
+                        self.notification = Some(Notification::new(format!(
+                            "+{} line{}",
+                            lines,
+                            if lines == 1 { "" } else { "s" }
+                        )));
+                        self.shape_selector
+                            .set_shapes(self.simulation.world().lines().to_vec());
                     }
                 }
             }
 
-            // Add score for all colors collected.
-            new_colors
-                .into_iter()
-                .for_each(|c| self.tally.borrow_mut().update(c));
-
-            // Update all port animations.
-            for port in self.world.ports_mut() {
-                *port.animation_mut() = match port.animation_mut() {
-                    Some(ref mut animation) => {
-                        animation.update(ctx, ());
-                        if animation.has_finished() {
-                            None
-                        } else {
-                            Some(*animation)
-                        }
-                    }
-                    None => None,
+            // Remove the notification once it has been shown.
+            let has_expired = match &mut self.notification {
+                Some(notification) => {
+                    notification.update(step, ());
+                    notification.has_expired()
                 }
+                None => false,
+            };
+            if has_expired {
+                self.notification = None;
             }
         }
 
         // If no routes, add pulsing animation to shape selector.
-        let num_routes = self.world.routes().count();
+        let num_routes = self.simulation.world().routes().count();
         *self.shape_selector.animation_mut() = match self.shape_selector.animation_mut() {
             Some(_) if num_routes != 0 => None,
             Some(ref mut animation) => {
-                animation.update(ctx, ());
+                animation.update(timer::get_delta(ctx), ());
                 Some(*animation)
             }
             None if num_routes == 0 => Some(Animation::new(
@@ -321,40 +317,25 @@ impl<'a> event::EventHandler for GameState<'a> {
             None => None,
         };
 
-        // Try to open a new port or some port import/export every 15s.
-        // TODO: Move magic constant.
-        if (timer::get_time_since_start(ctx) - self.last_progression_step).as_secs() > 15 {
-            self.last_progression_step = timer::get_time_since_start(ctx);
-
-            // Play sound effect.
-            self.audio_handler.play(SoundEffect::ProgressionStep);
-
-            // Ugly variable to avoid NLL not being available in stable yet.
-            let mut opened_new_port = false;
+        Ok(Transition::None)
+    }
 
-            // If we have more ports to add, do so and animate.
-            if let Some(new_port) = self.world.open_random_port(&mut self.rng) {
-                *new_port.animation_mut() = Some(Animation::new(
-                    Duration::new(1, 0),
-                    AnimationType::PulseScale {
-                        amplitude: 0.4,
-                        rate: 1.,
-                    },
-                ));
-                opened_new_port = true;
-            }
-            if !opened_new_port {
-                self.update_port_colors();
-            }
+    /// Skips the time passed while paused and acts on the choice made in
+    /// the pause menu.
+    fn resumed(&mut self, ctx: &mut Context) {
+        skip_update_time(ctx);
+        match self.pause_choice.take() {
+            Some(PauseChoice::Restart) => self.is_restarting = true,
+            Some(PauseChoice::QuitToMenu) => self.quit_to_menu(ctx),
+            Some(PauseChoice::Resume) | None => (),
         }
-        Ok(())
     }
 
     /// Handle mouse down events (drawing of paths etc.)
     fn mouse_button_down_event(
         &mut self,
         ctx: &mut Context,
-        _button: event::MouseButton,
+        button: event::MouseButton,
         x: i32,
         y: i32,
     ) {
@@ -372,20 +353,15 @@ impl<'a> event::EventHandler for GameState<'a> {
         );
 
         // Check if some mouse button on some shape.
-        let num_shapes = self.shape_selector.shapes().count() as u32;
-        let shape_selector_x_offset =
-            (self.config.grid_width as f32 / 2. - num_shapes as f32 + 1.) * cell_size;
-        let shape_selector_y_offset = (self.config.grid_height as f32 + 1.) * cell_size;
-
+        let num_shapes = self.shape_selector.shapes().count();
         for index in 0..num_shapes {
-            let shape_position = Position::new(
-                (shape_selector_x_offset + ((2. * index as f32 + 0.5) * cell_size)) as i32,
-                (shape_selector_y_offset + cell_size / 2.) as i32,
-            );
+            let center = self.shape_selector.shape_position(index, &self.config);
+            let shape_position =
+                Position::new((center.x * cell_size) as i32, (center.y * cell_size) as i32);
 
             // Check eucidean distance.
             if shape_position.distance(mouse_position) <= cell_size as f32 {
-                self.shape_selector.toggle(index as usize);
+                self.shape_selector.toggle(index);
                 println!("Toggling shape: {:?}", self.shape_selector.selected());
             }
         }
@@ -395,6 +371,10 @@ impl<'a> event::EventHandler for GameState<'a> {
         // TODO: Not the prettiest solution but hey.
         let mut has_selection_changed = false;
 
+        // Routes before any edit made by the click, to be able to undo it.
+        let routes_before = self.simulation.world().route_layouts();
+        let mut has_edited = false;
+
         // Not the prettiest code...
         let mut swap_builder = None;
         mem::swap(&mut self.ship_builder, &mut swap_builder);
@@ -403,31 +383,32 @@ impl<'a> event::EventHandler for GameState<'a> {
             Some(sb) => {
                 // Try to add ship to route (if any) on the mouse position.
                 // If it fails (and returns a ship), place it back on shipyard.
-                if let Some(builder) = sb.try_place(mouse_position_scaled, &mut self.world) {
-                    self.world.shipyard_mut().add_builder(builder);
+                if let Some(builder) =
+                    sb.try_place(mouse_position_scaled, self.simulation.world_mut())
+                {
+                    self.simulation
+                        .world_mut()
+                        .shipyard_mut()
+                        .add_builder(builder);
                 } else {
                     self.audio_handler.play(SoundEffect::PlaceShip);
+                    has_edited = true;
                 }
                 has_selection_changed = true;
                 None
             }
             _ => {
-                // Check if some mouse button on shipyard.
-                // TODO: Duplicated position logic.
-                let shipyard_x_offset = (self.config.grid_width as f32 / 2.) * cell_size;
-                let shipyard_y_offset = (self.config.grid_height as f32 + 2.5) * cell_size;
-
-                let shipyard_rect = graphics::Rect::new(
-                    shipyard_x_offset - cell_size,
-                    shipyard_y_offset - cell_size / 2.,
-                    cell_size * 2.5,
-                    cell_size,
+                // Check if some mouse button on a ship class in the shipyard.
+                let class = self.simulation.world().shipyard().class_at(
+                    ctx,
+                    &self.config,
+                    graphics::Point2::from(mouse_position),
                 );
 
-                // Check if player has any ship available.
-                if shipyard_rect.contains(graphics::Point2::from(mouse_position)) {
+                // Build if player has enough ships available.
+                if let Some(class) = class {
                     has_selection_changed = true;
-                    self.world.shipyard_mut().build()
+                    self.simulation.world_mut().shipyard_mut().build(class)
                 } else {
                     None
                 }
@@ -438,20 +419,48 @@ impl<'a> event::EventHandler for GameState<'a> {
             // Drawing already in progress, stop drawing.
             Some(rb) => {
                 // Remove all port animations.
-                for port in self.world.ports_mut() {
+                for port in self.simulation.world_mut().ports_mut() {
                     *port.animation_mut() = None;
                 }
 
                 has_selection_changed = true;
                 match self.shape_selector.selected() {
-                    Some(shape) if self.world.port(mouse_position_scaled).is_some() => {
-                        let allowed_ends = self.world.allowed_ends(*rb.from(), shape);
-
-                        if allowed_ends.iter().any(|p| *p == mouse_position_scaled) {
-                            if let Some(path) = rb.path() {
+                    Some(shape)
+                        if self
+                            .simulation
+                            .world()
+                            .port(mouse_position_scaled)
+                            .is_some() =>
+                    {
+                        if let Some(segment) = rb.segment() {
+                            // Insert the port into the dragged segment.
+                            let allowed_inserts = self.simulation.world().allowed_inserts(shape);
+
+                            if allowed_inserts.iter().any(|p| *p == mouse_position_scaled)
+                                && self.simulation.world_mut().insert_port(
+                                    shape,
+                                    segment,
+                                    mouse_position_scaled,
+                                )
+                            {
                                 self.audio_handler.play(SoundEffect::CreateRoute);
-                                self.world
-                                    .add_route(shape, *rb.from(), *rb.to(), path.clone())
+                                has_edited = true;
+                            }
+                        } else {
+                            let allowed_ends =
+                                self.simulation.world().allowed_ends(*rb.from(), shape);
+
+                            if allowed_ends.iter().any(|p| *p == mouse_position_scaled) {
+                                if let Some(path) = rb.path() {
+                                    self.audio_handler.play(SoundEffect::CreateRoute);
+                                    self.simulation.world_mut().add_route(
+                                        shape,
+                                        *rb.from(),
+                                        *rb.to(),
+                                        path.clone(),
+                                    );
+                                    has_edited = true;
+                                }
                             }
                         }
                         None
@@ -461,43 +470,101 @@ impl<'a> event::EventHandler for GameState<'a> {
             }
             // Start drawing a new path
             None => match self.shape_selector.selected() {
-                Some(ref shape) if self.world.port(mouse_position_scaled).is_some() => {
+                Some(ref shape)
+                    if self
+                        .simulation
+                        .world()
+                        .port(mouse_position_scaled)
+                        .is_some() =>
+                {
                     if self
-                        .world
+                        .simulation
+                        .world()
                         .allowed_starts(*shape)
                         .iter()
                         .any(|p| *p == mouse_position_scaled)
                     {
                         // Add port animations to valid end_points
-                        let allowed_ends = self.world.allowed_ends(mouse_position_scaled, *shape);
-                        for port in self.world.ports_mut() {
-                            if allowed_ends.iter().any(|p| *p == port.position()) {
-                                *port.animation_mut() = Some(Animation::new(
-                                    Duration::new(3600, 0),
-                                    AnimationType::PulseScale {
-                                        amplitude: 0.1,
-                                        rate: 1.,
-                                    },
-                                ));
-                            }
-                        }
+                        let allowed_ends = self
+                            .simulation
+                            .world()
+                            .allowed_ends(mouse_position_scaled, *shape);
+                        self.pulse_ports(&allowed_ends);
                         has_selection_changed = true;
                         Some(RouteBuilder::new(mouse_position_scaled))
                     } else {
                         None
                     }
                 }
+                // Start dragging a segment of the selected route.
+                Some(ref shape)
+                    if self.simulation.world().routes().any(|(s, route)| {
+                        s == shape && route.contains(Waypoint::from(mouse_position_scaled))
+                    }) =>
+                {
+                    // Add port animations to ports which can be inserted.
+                    let allowed_inserts = self.simulation.world().allowed_inserts(*shape);
+                    self.pulse_ports(&allowed_inserts);
+                    has_selection_changed = true;
+                    Some(RouteBuilder::on_segment(mouse_position_scaled))
+                }
                 _ => None,
             },
         };
 
-        // If click on some waypoint, remove all routes through and return ships to shipyard.
+        // If click on some waypoint, remove the links through it, or all routes through on right
+        // click, and return ships of removed routes to shipyard.
         if !has_selection_changed && self.route_builder.is_none() && self.ship_builder.is_none() {
-            let ships_removed = self
-                .world
-                .remove_routes_at(Waypoint::from(mouse_position_scaled));
+            let waypoint = Waypoint::from(mouse_position_scaled);
+            has_edited = self
+                .simulation
+                .world()
+                .routes()
+                .any(|(_, route)| route.contains(waypoint));
+            let ships_removed = match button {
+                event::MouseButton::Right => self.simulation.world_mut().remove_routes_at(waypoint),
+                _ => self.simulation.world_mut().remove_links_at(waypoint),
+            };
             for ship in ships_removed {
-                self.world.shipyard_mut().add_ship(ship);
+                self.simulation.world_mut().shipyard_mut().add_ship(ship);
+            }
+        }
+
+        if has_edited {
+            self.history.record(routes_before, self.simulation.world());
+        }
+    }
+
+    /// Handle key presses, pausing the game on escape and undoing or redoing
+    /// edits on Ctrl+Z and Ctrl+Y.
+    fn key_down_event(&mut self, _ctx: &mut Context, keycode: Keycode, keymod: Mod, repeat: bool) {
+        let is_ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+        match keycode {
+            Keycode::Escape if !repeat => self.is_pausing = true,
+            Keycode::Z if is_ctrl => {
+                self.cancel_builders();
+                self.history.undo(self.simulation.world_mut());
+            }
+            Keycode::Y if is_ctrl => {
+                self.cancel_builders();
+                self.history.redo(self.simulation.world_mut());
+            }
+            _ => (),
+        }
+    }
+
+    /// Pauses the game when the window loses focus.
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        if !gained {
+            self.is_pausing = true;
+        }
+    }
+
+    /// Saves the session if the window is closed before the game has ended.
+    fn quit_event(&mut self, ctx: &mut Context) {
+        if !self.is_game_over && !self.is_quitting {
+            if let Err(err) = save::save(ctx, &mut self.simulation) {
+                println!("Failed to save game: {}", err);
             }
         }
     }
@@ -520,28 +587,23 @@ impl<'a> event::EventHandler for GameState<'a> {
                     (self.config.scaling as f32 * x as f32 / cell_size) as i32,
                     (self.config.scaling as f32 * y as f32 / cell_size) as i32,
                 ),
-                &self.world,
+                self.simulation.world(),
             );
         }
     }
 
     /// Draws the current state to the screen with the given context.
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        if self.frames % 100 == 0 {
-            println!("FPS: {:.1}", timer::get_fps(ctx));
-        }
-
-        graphics::clear(ctx);
         self.sprite_drawer.clear();
 
         // Draw all base tiles.
-        for tile in self.world.tiles() {
+        for tile in self.simulation.world().tiles() {
             self.sprite_drawer
-                .draw_item(ctx, &self.config, tile, &self.world, true);
+                .draw_item(ctx, &self.config, tile, self.simulation.world(), true);
         }
 
         // Draw all routes.
-        for (shape, route) in self.world.routes() {
+        for (shape, route) in self.simulation.world().routes() {
             for waypoint in route.waypoints() {
                 self.sprite_drawer
                     .draw_item(ctx, &self.config, waypoint, shape, true);
@@ -560,13 +622,13 @@ impl<'a> event::EventHandler for GameState<'a> {
         }
 
         // Draw all ports.
-        for port in self.world.ports() {
+        for port in self.simulation.world().ports() {
             self.sprite_drawer
-                .draw_item(ctx, &self.config, port, &self.world, true);
+                .draw_item(ctx, &self.config, port, self.simulation.world(), true);
         }
 
         // Draw all ships.
-        for (_, route) in self.world.routes() {
+        for (_, route) in self.simulation.world().routes() {
             for ship in route.ships() {
                 // TODO: Must handle waypoints ending, and returning ships back.
                 self.sprite_drawer
@@ -590,29 +652,27 @@ impl<'a> event::EventHandler for GameState<'a> {
         self.sprite_drawer.paint(ctx, &self.config)?;
 
         // Draw tally.
-        self.tally
-            .borrow()
+        self.simulation
+            .tally()
             .paint(self.font_cache.medium(), ctx, &self.config)?;
 
         // Draw shipyard.
-        self.world
-            .shipyard_mut()
-            .paint(self.font_cache.small(), ctx, &self.config)?;
+        self.simulation.world_mut().shipyard_mut().paint(
+            self.font_cache.small(),
+            ctx,
+            &self.config,
+        )?;
+
+        // Draw notification (if any) next to the shipyard.
+        if let Some(notification) = &self.notification {
+            notification.paint(self.font_cache.small(), ctx, &self.config)?;
+        }
 
         // Draw remaining game time.
-        self.game_timer
+        self.simulation
+            .game_timer()
             .paint(self.font_cache.medium(), ctx, &self.config)?;
 
-        graphics::present(ctx);
-        self.frames += 1;
-        // And yield the timeslice
-        // This tells the OS that we're done using the CPU but it should
-        // get back to this program as soon as it can.
-        // This ideally prevents the game from using 100% CPU all the time
-        // even if vsync is off.
-        // The actual behavior can be a little platform-specific.
-        timer::yield_now();
-
         Ok(())
     }
 }
@@ -119,3 +119,50 @@ impl PartialEq for OrdPosition {
 }
 
 impl Eq for OrdPosition {}
+
+/// Serialization of points as a pair of coordinates, for use with
+/// `#[serde(with = "::geometry::serde_point")]`.
+pub mod serde_point {
+    use ggez::graphics::Point2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serializes the point as its x and y coordinates.
+    pub fn serialize<S: Serializer>(point: &Point2, serializer: S) -> Result<S::Ok, S::Error> {
+        (point.x, point.y).serialize(serializer)
+    }
+
+    /// Deserializes a point from its x and y coordinates.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point2, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Point2::new(x, y))
+    }
+}
+
+/// Serialization of maps keyed by position as a list of entries, since
+/// formats such as JSON only allow string keys. For use with
+/// `#[serde(with = "::geometry::serde_position_map")]`.
+pub mod serde_position_map {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    use super::Position;
+
+    /// Serializes the map as a list of position and value pairs.
+    pub fn serialize<V, S>(map: &HashMap<Position, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Serialize,
+        S: Serializer,
+    {
+        map.iter().collect::<Vec<_>>().serialize(serializer)
+    }
+
+    /// Deserializes the map from a list of position and value pairs.
+    pub fn deserialize<'de, V, D>(deserializer: D) -> Result<HashMap<Position, V>, D::Error>
+    where
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let entries = Vec::<(Position, V)>::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
@@ -0,0 +1,354 @@
+use ggez::{
+    graphics::{draw, get_drawable_size, set_color, Color as ggezColor, Font, Point2, Text},
+    Context, GameResult,
+};
+use serde_json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use config::{Difficulty, GameMode};
+use save::SaveError;
+use simulation::EndReason;
+use tally::Tally;
+
+/// Path in the user directory of the high-score table.
+pub const HIGH_SCORE_PATH: &str = "/highscores.json";
+
+/// Number of results kept in the high-score table.
+const MAX_ENTRIES: usize = 10;
+
+/// Returns the given number of seconds since the unix epoch as a
+/// YYYY-MM-DD date.
+fn format_date(seconds: u64) -> String {
+    // Convert days since epoch to a civil date (proleptic Gregorian calendar).
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The result of a single finished game session.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HighScoreEntry {
+    score: u32,
+    tally: Tally,
+    date: String,
+    map: String,
+    seed: u64,
+    /// Difficulty played, as the preset or custom.
+    #[serde(default)]
+    difficulty: Difficulty,
+    /// Mode played, results are only ranked against the same mode.
+    #[serde(default)]
+    mode: GameMode,
+    /// Seconds of game time played.
+    #[serde(default)]
+    seconds: u64,
+    /// Reason the session ended, unknown for entries of older versions.
+    #[serde(default)]
+    end_reason: Option<EndReason>,
+}
+
+impl HighScoreEntry {
+    /// Creates a new entry for a session of the given difficulty and mode
+    /// finished now for the given reason, after the given seconds of game
+    /// time.
+    pub fn new(
+        tally: Tally,
+        map: String,
+        seed: u64,
+        difficulty: Difficulty,
+        mode: GameMode,
+        seconds: u64,
+        end_reason: Option<EndReason>,
+    ) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        HighScoreEntry {
+            score: tally.score(),
+            tally,
+            date: format_date(now),
+            map,
+            seed,
+            difficulty,
+            mode,
+            seconds,
+            end_reason,
+        }
+    }
+
+    /// Returns the score.
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// Returns the amount of each color collected.
+    pub fn tally(&self) -> &Tally {
+        &self.tally
+    }
+
+    /// Returns the date the session was finished, as YYYY-MM-DD.
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Returns the title of the map played.
+    pub fn map(&self) -> &str {
+        &self.map
+    }
+
+    /// Returns the seed of the session.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the difficulty played.
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Returns the mode played.
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Returns the seconds of game time played.
+    pub fn seconds(&self) -> u64 {
+        self.seconds
+    }
+
+    /// Returns the reason the session ended, if known.
+    pub fn end_reason(&self) -> Option<EndReason> {
+        self.end_reason
+    }
+
+    /// Returns true if the session completed the goal of its mode, which
+    /// only target score sessions fail by being lost before the target.
+    pub fn is_completed(&self) -> bool {
+        match (self.mode, self.end_reason) {
+            (GameMode::TargetScore(_), Some(reason)) => reason == EndReason::TargetReached,
+            // Entries of older versions only tell by the score.
+            (GameMode::TargetScore(target), None) => self.score >= target,
+            _ => true,
+        }
+    }
+
+    /// Returns the text describing the result, as given by the mode.
+    pub fn result_text(&self) -> String {
+        self.mode
+            .result_text(self.score, self.seconds, self.is_completed())
+    }
+
+    /// Returns true if the entry ranks above the other entry of the same
+    /// mode, either by a higher score or by a shorter time. Sessions which
+    /// did not complete a mode ranked by time rank below those which did,
+    /// by their score.
+    fn is_better_than(&self, other: &HighScoreEntry) -> bool {
+        if !self.mode.is_ranked_by_time() {
+            return self.score > other.score;
+        }
+        match (self.is_completed(), other.is_completed()) {
+            (true, true) => self.seconds < other.seconds,
+            (false, false) => self.score > other.score,
+            (is_completed, _) => is_completed,
+        }
+    }
+}
+
+/// Table of the best results of each mode, ordered by score or time.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HighScores {
+    entries: Vec<HighScoreEntry>,
+}
+
+impl HighScores {
+    /// Loads the high-score table from the user directory, starting a new
+    /// table if there is none.
+    pub fn load(ctx: &mut Context) -> Result<Self, SaveError> {
+        if !ctx.filesystem.is_file(HIGH_SCORE_PATH) {
+            return Ok(HighScores::default());
+        }
+        let file = ctx.filesystem.open(HIGH_SCORE_PATH)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Saves the high-score table to the user directory.
+    pub fn save(&self, ctx: &mut Context) -> Result<(), SaveError> {
+        let file = ctx.filesystem.create(HIGH_SCORE_PATH)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Returns all entries of the given mode, best first.
+    pub fn entries(&self, mode: GameMode) -> Vec<&HighScoreEntry> {
+        self.entries.iter().filter(|e| e.mode == mode).collect()
+    }
+
+    /// Inserts the entry, returning its rank (zero being the best) among
+    /// entries of the same mode if it made it into the table. Earlier
+    /// entries win ties.
+    pub fn insert(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        let rank = self
+            .entries(entry.mode)
+            .iter()
+            .position(|e| entry.is_better_than(e))
+            .unwrap_or_else(|| self.entries(entry.mode).len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        // Insert before the entry it beats, or last if it beats none.
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.mode == entry.mode && entry.is_better_than(e))
+            .unwrap_or(self.entries.len());
+        let mode = entry.mode;
+        self.entries.insert(index, entry);
+
+        // Only keep the best entries of the mode.
+        let mut kept = 0;
+        self.entries.retain(|e| {
+            if e.mode != mode {
+                return true;
+            }
+            kept += 1;
+            kept <= MAX_ENTRIES
+        });
+        Some(rank)
+    }
+
+    /// Draws the table of the given mode at the given height, highlighting
+    /// the entry of the given rank.
+    /// Does not implement Drawable since it is unable to be drawn using a
+    /// spritebatch.
+    pub fn paint(
+        &self,
+        font: &Font,
+        ctx: &mut Context,
+        y_offset: f32,
+        mode: GameMode,
+        highlight: Option<usize>,
+    ) -> GameResult<()> {
+        let (window_width, _) = get_drawable_size(ctx);
+        let x_offset = window_width as f32 * 0.15;
+
+        let entries = self.entries(mode);
+        let mut y_offset = y_offset;
+        if entries.is_empty() {
+            set_color(ctx, ggezColor::from_rgb(69, 55, 52))?;
+            let text = Text::new(ctx, "No high scores yet", font)?;
+            draw(ctx, &text, Point2::new(x_offset, y_offset), 0.)?;
+        }
+        for (rank, entry) in entries.into_iter().enumerate() {
+            // Highlight the given entry in red, otherwise use blackish color.
+            let (r, g, b) = if highlight == Some(rank) {
+                (232, 66, 54)
+            } else {
+                (69, 55, 52)
+            };
+            set_color(ctx, ggezColor::from_rgb(r, g, b))?;
+
+            // Show the time taken in modes ranked by time if completed,
+            // otherwise the score.
+            let result = if entry.mode.is_ranked_by_time() && entry.is_completed() {
+                format!("{}:{:02}", entry.seconds / 60, entry.seconds % 60)
+            } else {
+                entry.score.to_string()
+            };
+            let collected = entry
+                .tally
+                .entries()
+                .iter()
+                .map(|(_, amount)| format!("{:3}", amount))
+                .collect::<Vec<_>>();
+            let text = Text::new(
+                ctx,
+                &format!(
+                    "{:2}. {:>5}  {}  {:6}  {:10}  {}  #{}",
+                    rank + 1,
+                    result,
+                    collected.join("/"),
+                    entry.difficulty.label(),
+                    entry.date,
+                    entry.map,
+                    entry.seed
+                ),
+                font,
+            )?;
+            draw(ctx, &text, Point2::new(x_offset, y_offset), 0.)?;
+            y_offset += text.height() as f32 * 1.2;
+        }
+        // Reset color to default (white).
+        set_color(ctx, ggezColor::from_rgb(255, 255, 255))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color::Color;
+    use geometry::Position;
+
+    /// Creates an entry of a target score session with the given score,
+    /// seconds played and end reason.
+    fn target_entry(score: u32, seconds: u64, end_reason: EndReason) -> HighScoreEntry {
+        let color = Color::from_name("Blue").unwrap();
+        let mut tally = Tally::new(&[color]);
+        tally.update(color, score);
+        HighScoreEntry::new(
+            tally,
+            "Strait".to_owned(),
+            1,
+            Difficulty::Normal,
+            GameMode::TargetScore(10),
+            seconds,
+            Some(end_reason),
+        )
+    }
+
+    #[test]
+    fn ranks_lost_target_sessions_below_completed_ones() {
+        let mut high_scores = HighScores::default();
+        let lost = target_entry(3, 20, EndReason::Overload(Position::new(0, 0)));
+        assert!(!lost.is_completed());
+        assert!(lost.result_text().contains("Score: 3 of 10"));
+        assert_eq!(high_scores.insert(lost), Some(0));
+
+        let completed = target_entry(10, 300, EndReason::TargetReached);
+        assert!(completed.result_text().starts_with("Reached 10 in 5:00"));
+        assert_eq!(high_scores.insert(completed), Some(0));
+        assert_eq!(
+            high_scores.insert(target_entry(10, 200, EndReason::TargetReached)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(target_entry(
+                5,
+                10,
+                EndReason::Overload(Position::new(0, 0))
+            )),
+            Some(2)
+        );
+
+        let seconds = high_scores
+            .entries(GameMode::TargetScore(10))
+            .iter()
+            .map(|entry| entry.seconds())
+            .collect::<Vec<_>>();
+        assert_eq!(seconds, vec![200, 300, 10, 20]);
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use route::{RouteLayout, RouteShape};
+use world::World;
+
+/// Maximum number of edits which can be undone.
+const MAX_EDITS: usize = 50;
+
+/// An edit of the routes and their ships, as the layout of the routes
+/// before and after, ships and their cargo included.
+#[derive(Debug, Clone)]
+struct Edit {
+    before: HashMap<RouteShape, RouteLayout>,
+    after: HashMap<RouteShape, RouteLayout>,
+}
+
+/// Keeps the history of edits to routes and ships, which can be undone
+/// and redone.
+#[derive(Debug, Clone, Default)]
+pub struct EditHistory {
+    undone: Vec<Edit>,
+    done: Vec<Edit>,
+}
+
+impl EditHistory {
+    /// Creates a new empty history.
+    pub fn new() -> Self {
+        EditHistory::default()
+    }
+
+    /// Records an edit of the routes of the world, given the layout of the
+    /// routes before it. Edits undone before are forgotten, unless nothing
+    /// was changed.
+    pub fn record(&mut self, before: HashMap<RouteShape, RouteLayout>, world: &World) {
+        let after = world.route_layouts();
+        if before == after {
+            return;
+        }
+        self.undone.clear();
+        self.done.push(Edit { before, after });
+        if self.done.len() > MAX_EDITS {
+            self.done.remove(0);
+        }
+    }
+
+    /// Restores the routes of the world as they were before the last edit.
+    /// Returns true if an edit was undone.
+    pub fn undo(&mut self, world: &mut World) -> bool {
+        match self.done.pop() {
+            Some(edit) => {
+                if world.restore_routes(edit.before.clone()) {
+                    self.undone.push(edit);
+                    true
+                } else {
+                    self.done.push(edit);
+                    false
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// Restores the routes of the world as they were after the last edit
+    /// undone. Returns true if an edit was redone.
+    pub fn redo(&mut self, world: &mut World) -> bool {
+        match self.undone.pop() {
+            Some(edit) => {
+                if world.restore_routes(edit.after.clone()) {
+                    self.done.push(edit);
+                    true
+                } else {
+                    self.undone.push(edit);
+                    false
+                }
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color::Color;
+    use geometry::Position;
+    use map::{generator::seeded_rng, MapData};
+    use route::Waypoint;
+    use ship::ShipClass;
+
+    /// Creates a world of a strait with five open ports, the first two
+    /// linked by a route with a ship docked at the first port.
+    fn world() -> (World, RouteShape, Vec<Position>) {
+        let mut world = MapData::strait(&[("Blue", "Red", 0); 5])
+            .into_world(&mut seeded_rng(0))
+            .unwrap();
+        let ports = world
+            .ports()
+            .iter()
+            .map(|p| p.position())
+            .collect::<Vec<_>>();
+        let shape = world.lines()[0];
+        let (_, path) = world.route(ports[0], ports[1]).unwrap();
+        world.add_route(
+            shape,
+            ports[0],
+            ports[1],
+            path.into_iter().map(Waypoint::from).collect(),
+        );
+        let builder = world.shipyard_mut().build(ShipClass::Sloop).unwrap();
+        assert!(builder.try_place(ports[0], &mut world).is_none());
+        (world, shape, ports)
+    }
+
+    /// Loads a unit of cargo onto every ship.
+    fn load_ships(world: &mut World) {
+        let color = Color::from_name("Red").unwrap();
+        for (_, route) in world.routes_mut() {
+            for ship in route.ships_mut() {
+                ship.try_load(color, 1);
+            }
+        }
+    }
+
+    /// Returns the amount of cargo on all ships.
+    fn cargo(world: &World) -> u32 {
+        world
+            .routes()
+            .flat_map(|(_, route)| route.ships())
+            .map(|ship| ship.cargo().amount())
+            .sum()
+    }
+
+    #[test]
+    fn keeps_cargo_of_remaining_ships() {
+        let (mut world, shape, ports) = world();
+        let mut history = EditHistory::new();
+        let before = world.route_layouts();
+        let (_, path) = world.route(ports[1], ports[2]).unwrap();
+        world.add_route(
+            shape,
+            ports[1],
+            ports[2],
+            path.into_iter().map(Waypoint::from).collect(),
+        );
+        history.record(before, &world);
+
+        // Cargo loaded after the edit stays on board.
+        load_ships(&mut world);
+        assert!(history.undo(&mut world));
+        assert_eq!(cargo(&world), 1);
+        assert!(history.redo(&mut world));
+        assert_eq!(cargo(&world), 1);
+        let (_, route) = world.routes().next().unwrap();
+        assert_eq!(route.ports().count(), 3);
+    }
+
+    #[test]
+    fn restores_removed_ships_with_cargo() {
+        let (mut world, _, ports) = world();
+        let mut history = EditHistory::new();
+        load_ships(&mut world);
+        let available = world.shipyard().available();
+
+        let before = world.route_layouts();
+        for ship in world.remove_routes_at(Waypoint::from(ports[0])) {
+            world.shipyard_mut().add_ship(ship);
+        }
+        history.record(before, &world);
+        assert_eq!(world.shipyard().available(), available + 1);
+
+        // The ship is restored with the cargo it carried.
+        assert!(history.undo(&mut world));
+        assert_eq!(world.shipyard().available(), available);
+        let (_, route) = world.routes().next().unwrap();
+        let ship = route.ships().next().unwrap();
+        assert_eq!(ship.position(), Waypoint::from(ports[0]));
+        assert_eq!(cargo(&world), 1);
+    }
+
+    #[test]
+    fn restores_ships_moved_to_split_off_route() {
+        let (mut world, shape, ports) = world();
+        let mut history = EditHistory::new();
+        for pair in ports.windows(2).skip(1) {
+            let (_, path) = world.route(pair[0], pair[1]).unwrap();
+            world.add_route(
+                shape,
+                pair[0],
+                pair[1],
+                path.into_iter().map(Waypoint::from).collect(),
+            );
+        }
+        load_ships(&mut world);
+        let available = world.shipyard().available();
+        let ids = |world: &World| {
+            let mut ids = world
+                .routes()
+                .flat_map(|(_, route)| route.ships())
+                .map(|ship| ship.id())
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        let ships = ids(&world);
+
+        // Removing the second link moves the ship to the first, split off
+        // onto a free line.
+        let before = world.route_layouts();
+        let link = world.route(ports[1], ports[2]).unwrap().1[2];
+        assert!(world.remove_links_at(Waypoint::from(link)).is_empty());
+        history.record(before, &world);
+        assert_eq!(world.routes().count(), 2);
+        assert!(world
+            .routes()
+            .any(|(line, route)| *line != shape && route.ships().count() == 1));
+
+        // Undoing takes the ship back, cargo included, without building another.
+        assert!(history.undo(&mut world));
+        assert_eq!(world.routes().count(), 1);
+        assert_eq!(ids(&world), ships);
+        assert_eq!(cargo(&world), 1);
+        assert_eq!(world.shipyard().available(), available);
+        assert!(history.redo(&mut world));
+        assert_eq!(world.routes().count(), 2);
+        assert_eq!(ids(&world), ships);
+        assert_eq!(cargo(&world), 1);
+        assert_eq!(world.shipyard().available(), available);
+    }
+}
@@ -1,23 +1,37 @@
 #[macro_use]
 extern crate serde_derive;
 extern crate ggez;
+#[macro_use]
+extern crate lazy_static;
 extern crate rand;
 extern crate serde;
 extern crate serde_json;
 
 pub mod animation;
 pub mod audio;
+mod benchmark;
 pub mod color;
 pub mod config;
 pub mod draw;
+mod editorstate;
 pub mod fonts;
 mod gamestate;
 pub mod geometry;
+pub mod highscore;
+pub mod history;
+pub mod map;
 pub mod menustate;
+pub mod notification;
+mod pausestate;
 pub mod port;
+pub mod progression;
+pub mod resource;
 pub mod route;
+pub mod save;
+pub mod scene;
 pub mod scorestate;
 pub mod ship;
+pub mod simulation;
 pub mod tally;
 pub mod tile;
 pub mod time;
@@ -25,7 +39,7 @@ pub mod update;
 pub mod world;
 
 use ggez::{conf, event, Context};
-use std::{cell::RefCell, env, path::PathBuf};
+use std::{env, path::PathBuf};
 
 static GAME_ID: &str = "hansa";
 static AUTHOR: &str = "holmgr";
@@ -67,6 +81,66 @@ pub fn main() -> ggez::GameResult<()> {
     if args.iter().any(|ref arg| arg.as_str() == "--fuck-apple") {
         config.scaling = 2;
     }
+    if let Some(index) = args.iter().position(|arg| arg.as_str() == "--seed") {
+        // Seed of the game session, for replaying a session exactly.
+        config.seed = args
+            .get(index + 1)
+            .and_then(|seed| seed.parse::<u64>().ok());
+    }
+    if let Some(index) = args.iter().position(|arg| arg.as_str() == "--difficulty") {
+        // Difficulty by name, keeping the default if not recognized.
+        if let Some(difficulty) = args.get(index + 1).and_then(|name| {
+            config::Difficulty::values()
+                .into_iter()
+                .find(|d| d.name() == name.as_str())
+        }) {
+            config.difficulty = difficulty;
+        }
+    }
+    // Custom difficulty settings, choosing the custom difficulty if any is given.
+    let setting = |flag: &str| {
+        args.iter()
+            .position(|arg| arg.as_str() == flag)
+            .and_then(|index| args.get(index + 1))
+            .and_then(|value| value.parse::<u32>().ok())
+    };
+    let custom = config.custom_difficulty;
+    config.custom_difficulty = config::DifficultySettings {
+        port_interval: setting("--port-interval").map_or(custom.port_interval, u64::from),
+        initial_ships: setting("--initial-ships").map_or(custom.initial_ships, |v| v as usize),
+        ship_speed: setting("--ship-speed").unwrap_or(custom.ship_speed),
+        docking_time: setting("--docking-time").unwrap_or(custom.docking_time),
+        colors: setting("--colors").map_or(custom.colors, |v| v as usize),
+    };
+    if config.custom_difficulty != custom {
+        config.difficulty = config::Difficulty::Custom;
+    }
+    if let Some(index) = args.iter().position(|arg| arg.as_str() == "--mode") {
+        // Game mode by name, optionally followed by the session length in
+        // seconds or the target score. Keeps the default if not recognized.
+        if let Some(mode) = args.get(index + 1).and_then(|name| {
+            let parameter = args
+                .get(index + 2)
+                .and_then(|parameter| parameter.parse::<u32>().ok());
+            config::GameMode::from_name(name, parameter)
+        }) {
+            config.game_mode = mode;
+        }
+    }
+    if let Some(index) = args.iter().position(|arg| arg.as_str() == "--generate") {
+        // Use the given seed, or the session seed (if any) or a random one if omitted.
+        config.map_seed = Some(
+            args.get(index + 1)
+                .and_then(|seed| seed.parse::<u64>().ok())
+                .or(config.seed)
+                .unwrap_or_else(rand::random),
+        );
+    }
+    if args.iter().any(|arg| arg.as_str() == "--benchmark") {
+        // Run headless benchmark on a large generated map and exit.
+        benchmark::run(400, 200, config.map_seed.or(config.seed).unwrap_or(0));
+        return Ok(());
+    }
 
     // Create game context.
     let mut ctx = load_context();
@@ -79,21 +153,10 @@ pub fn main() -> ggez::GameResult<()> {
     }
     set_optimal_resolution(&mut ctx);
 
-    // Start main menu.
-    let menu_state = &mut menustate::MenuState::new(&mut ctx, config)?;
-
-    // Run main menu until clean exit.
-    event::run(&mut ctx, menu_state)?;
-
-    let tally = RefCell::new(tally::Tally::new());
-
-    // Start game, run until completion.
-    let game_state = &mut gamestate::GameState::new(&mut ctx, config, &tally)?;
-    event::run(&mut ctx, game_state)?;
-
-    // Start score state.
-    let score_state = &mut scorestate::ScoreState::new(&mut ctx, &tally)?;
-    let result = event::run(&mut ctx, score_state);
+    // Start with the main menu, run until clean exit.
+    let menu_state = menustate::MenuState::new(&mut ctx, config)?;
+    let scene_stack = &mut scene::SceneStack::new(Box::new(menu_state));
+    let result = event::run(&mut ctx, scene_stack);
     println!("{:?}", result);
     result
 }
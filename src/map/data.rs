@@ -0,0 +1,385 @@
+use rand::Rng;
+use serde_json;
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    time::Duration,
+};
+
+use super::{MapError, PpmImage};
+use color::Color;
+use geometry::Position;
+use port::Port;
+use progression::Progression;
+use route::{find_path, NavigationGrid};
+use tile::{Tile, TileKind};
+use world::World;
+
+/// Time of a single game session unless specified by the map: 5min.
+const DEFAULT_SESSION_LENGTH: u64 = 60 * 5;
+
+/// Descriptive information about a map.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MapMetadata {
+    pub title: String,
+    pub author: String,
+    /// Length of a game session in seconds.
+    pub session_length: u64,
+}
+
+impl Default for MapMetadata {
+    fn default() -> Self {
+        MapMetadata {
+            title: "Untitled".to_owned(),
+            author: String::new(),
+            session_length: DEFAULT_SESSION_LENGTH,
+        }
+    }
+}
+
+/// Definition of a single port on a map.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PortDefinition {
+    pub name: String,
+    pub position: Position,
+    /// Initial import color, sampled when the map is loaded if omitted.
+    #[serde(default)]
+    pub import: Option<Color>,
+    /// Initial export color, sampled when the map is loaded if omitted.
+    #[serde(default)]
+    pub export: Option<Color>,
+    /// Seconds into the session when the port opens, zero meaning open
+    /// from the start. Ports without an open time are opened by the
+    /// regular progression.
+    #[serde(default)]
+    pub open_time: Option<u64>,
+}
+
+impl PortDefinition {
+    /// Returns whether the port is open from the start of the session.
+    pub fn is_initially_open(&self) -> bool {
+        self.open_time == Some(0)
+    }
+}
+
+/// A structured map, holding the tile grid, ports and metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MapData {
+    pub metadata: MapMetadata,
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<Tile>,
+    pub ports: Vec<PortDefinition>,
+    /// Pacing of sessions on the map, the default progression if omitted.
+    #[serde(default)]
+    pub progression: Option<Progression>,
+}
+
+impl MapData {
+    /// Imports a map from an image, mapping RGB to tiles.
+    /// Pure blue is water, pure red a closed port and pure green an open port,
+    /// everything else is land.
+    pub fn from_image(image: &PpmImage) -> Self {
+        let mut tiles = vec![];
+        let mut ports = vec![];
+
+        for (i, pixel) in image.pixels().iter().enumerate() {
+            let index = i as i32;
+            let position =
+                Position::new(index % image.width() as i32, index / image.width() as i32);
+            let open_time = match pixel {
+                (0, 0, 255) => {
+                    tiles.push(Tile::new(position, TileKind::Water));
+                    continue;
+                }
+                (255, 0, 0) => None,
+                (0, 255, 0) => Some(0),
+                _ => {
+                    tiles.push(Tile::new(position, TileKind::Land));
+                    continue;
+                }
+            };
+            tiles.push(Tile::new(position, TileKind::Land));
+            ports.push(PortDefinition {
+                name: format!("Port {}", ports.len() + 1),
+                position,
+                import: None,
+                export: None,
+                open_time,
+            });
+        }
+
+        MapData {
+            metadata: MapMetadata::default(),
+            width: image.width(),
+            height: image.height(),
+            tiles,
+            ports,
+            progression: None,
+        }
+    }
+
+    /// Creates a map of ports along a strait, for tests. Ports are given by
+    /// the names of their import and export colors and their open time, and
+    /// are placed four tiles apart on the land north of the strait. The
+    /// progression has no events, so only scheduled ports open.
+    #[cfg(test)]
+    pub fn strait(ports: &[(&str, &str, u64)]) -> Self {
+        let width = 4 * ports.len() as u32 + 1;
+        let tiles = (0..width as i32 * 3)
+            .map(|i| {
+                let position = Position::new(i % width as i32, i / width as i32);
+                let kind = if position.y == 1 {
+                    TileKind::Water
+                } else {
+                    TileKind::Land
+                };
+                Tile::new(position, kind)
+            }).collect();
+        let ports = ports
+            .iter()
+            .enumerate()
+            .map(|(i, (import, export, open_time))| PortDefinition {
+                name: format!("Port {}", i + 1),
+                position: Position::new(4 * i as i32 + 2, 0),
+                import: Color::from_name(import),
+                export: Color::from_name(export),
+                open_time: Some(*open_time),
+            }).collect();
+        MapData {
+            metadata: MapMetadata::default(),
+            width,
+            height: 3,
+            tiles,
+            ports,
+            progression: Some(Progression {
+                events: vec![],
+                ..Progression::default()
+            }),
+        }
+    }
+
+    /// Reads a map from JSON, checking that it is well formed.
+    pub fn read<R: Read>(reader: R) -> Result<Self, MapError> {
+        let map: MapData = serde_json::from_reader(reader)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Writes the map as JSON.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), MapError> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Checks that every grid position has exactly one tile, that all
+    /// ports are placed on land and that no port imports what it exports.
+    pub fn validate(&self) -> Result<(), MapError> {
+        let in_grid = |position: Position| {
+            position.x >= 0
+                && position.y >= 0
+                && (position.x as u32) < self.width
+                && (position.y as u32) < self.height
+        };
+
+        let mut covered = HashSet::new();
+        for tile in &self.tiles {
+            if !in_grid(tile.position()) || !covered.insert(tile.position()) {
+                return Err(MapError::InvalidMap(format!(
+                    "tile at {:?} is outside the grid or duplicated",
+                    tile.position()
+                )));
+            }
+        }
+        let size = u64::from(self.width) * u64::from(self.height);
+        if covered.len() as u64 != size {
+            return Err(MapError::InvalidMap(format!(
+                "expected {} tiles but found {}",
+                size,
+                covered.len()
+            )));
+        }
+
+        let mut port_positions = HashSet::new();
+        for port in &self.ports {
+            let on_land = self
+                .tiles
+                .iter()
+                .any(|t| t.position() == port.position && t.kind() == TileKind::Land);
+            if !on_land || !port_positions.insert(port.position) {
+                return Err(MapError::InvalidMap(format!(
+                    "port '{}' is not on land or shares position with another port",
+                    port.name
+                )));
+            }
+            if port.import.is_some() && port.import == port.export {
+                return Err(MapError::InvalidMap(format!(
+                    "port '{}' imports the color it exports",
+                    port.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every port can reach every other port by sea.
+    pub fn check_connectivity(&self) -> Result<(), MapError> {
+        // Colors do not affect pathfinding, all ports are treated as open.
+        let ports = self
+            .ports
+            .iter()
+            .map(|p| Port::new(p.position, Color::default(), Color::default()))
+            .collect::<Vec<_>>();
+        let grid = NavigationGrid::new(&self.tiles, &ports);
+        if let Some((first, rest)) = self.ports.split_first() {
+            for port in rest {
+                let path = find_path(&grid, &HashSet::new(), first.position, port.position);
+                if path.is_none() {
+                    return Err(MapError::InvalidMap(format!(
+                        "port '{}' cannot reach port '{}'",
+                        first.name, port.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the progression of the map, or the default progression if
+    /// the map has none.
+    pub fn progression(&self) -> Progression {
+        self.progression.clone().unwrap_or_default()
+    }
+
+    /// Creates a world from the map, sampling any colors left unspecified
+    /// out of the colors traded from the start.
+    pub fn into_world<R: Rng>(self, color_sampler: &mut R) -> Result<World, MapError> {
+        let progression = self.progression();
+
+        // Colors given by port definitions are traded as well.
+        let mut colors = vec![];
+        let defined_colors = self
+            .ports
+            .iter()
+            .flat_map(|p| p.import.into_iter().chain(p.export));
+        for color in progression.colors.into_iter().chain(defined_colors) {
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+        if colors.len() < 2 {
+            return Err(MapError::InvalidMap(
+                "progression must trade at least two colors".to_owned(),
+            ));
+        }
+
+        // Generate order of import, export colors for inital ports.
+        let (mut imports, mut exports) = (colors.clone(), colors.clone());
+        loop {
+            color_sampler.shuffle(&mut imports);
+            color_sampler.shuffle(&mut exports);
+
+            // Reshuffle until valid import/export combinations
+            if imports.iter().zip(exports.iter()).all(|(i, e)| i != e) {
+                break;
+            }
+        }
+
+        let mut open_ports = vec![];
+        let mut closed_ports = vec![];
+        let mut names = vec![];
+        let mut schedule = vec![];
+        for port in self.ports {
+            let (import, export) = match (port.import, port.export) {
+                (Some(import), Some(export)) => (import, export),
+                // Only the color left unspecified is sampled.
+                (Some(import), None) => (
+                    import,
+                    Port::sample_other_color(color_sampler, &colors, import),
+                ),
+                (None, Some(export)) => (
+                    Port::sample_other_color(color_sampler, &colors, export),
+                    export,
+                ),
+                // Open ports trade each color first, any further are sampled.
+                _ if port.is_initially_open() && !imports.is_empty() => {
+                    (imports.pop().unwrap(), exports.pop().unwrap())
+                }
+                _ => Port::sample_colors(color_sampler, &colors),
+            };
+            if import == export {
+                return Err(MapError::InvalidMap(format!(
+                    "port '{}' imports the color it exports",
+                    port.name
+                )));
+            }
+            if port.is_initially_open() {
+                open_ports.push(Port::new(port.position, import, export));
+            } else {
+                closed_ports.push(Port::new(port.position, import, export));
+            }
+            if let Some(time) = port.open_time.filter(|time| *time > 0) {
+                schedule.push((Duration::from_secs(time), port.position));
+            }
+            names.push((port.position, port.name));
+        }
+
+        // Keep tiles in row-major order.
+        let mut tiles = self.tiles;
+        tiles.sort_by_key(|t| (t.position().y, t.position().x));
+
+        let mut world = World::new(
+            tiles.into_iter(),
+            open_ports.into_iter(),
+            closed_ports.into_iter(),
+        );
+        for (position, name) in names {
+            world.name_port(position, name);
+        }
+        for (time, position) in schedule {
+            world.schedule_port(time, position);
+        }
+        for color in colors {
+            world.add_color(color);
+        }
+        world.add_lines(progression.lines);
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::generator::seeded_rng;
+
+    #[test]
+    fn samples_only_unspecified_colors() {
+        let blue = Color::from_name("Blue");
+        for seed in 0..20 {
+            let mut map = MapData::strait(&[("Blue", "Red", 0), ("Red", "Blue", 0)]);
+            map.ports[0].export = None;
+            map.ports[1].import = None;
+            let world = map.into_world(&mut seeded_rng(seed)).unwrap();
+            let ports = world.ports();
+            assert_eq!(Some(ports[0].import()), blue);
+            assert_ne!(ports[0].export(), ports[0].import());
+            assert_eq!(Some(ports[1].export()), blue);
+            assert_ne!(ports[1].import(), ports[1].export());
+        }
+    }
+
+    #[test]
+    fn rejects_port_importing_its_export() {
+        let map = MapData::strait(&[("Blue", "Blue", 0), ("Red", "Blue", 0)]);
+        assert!(map.validate().is_err());
+        assert!(map.into_world(&mut seeded_rng(0)).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_grid() {
+        let mut map = MapData::strait(&[("Blue", "Red", 0), ("Red", "Blue", 0)]);
+        map.width = u32::max_value();
+        map.height = 3;
+        assert!(map.validate().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashSet, VecDeque};
+
+use super::{MapData, MapError, MapMetadata, PortDefinition};
+use color::Color;
+use geometry::Position;
+use tile::{Tile, TileKind};
+use world::World;
+
+/// Number of noise layers summed for the land height map.
+const OCTAVES: u32 = 4;
+/// Size in tiles of the coarsest noise layer's lattice cells.
+const BASE_CELL_SIZE: u32 = 16;
+/// Number of tiles from the map edge over which land fades into water.
+const EDGE_MARGIN: f32 = 4.;
+/// Number of maps tried before giving up on the given parameters.
+const MAX_ATTEMPTS: usize = 32;
+
+/// Parameters for generating an archipelago map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneratorSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Fraction of tiles which are land, between 0 and 1.
+    pub land_ratio: f32,
+    pub open_ports: usize,
+    pub closed_ports: usize,
+    /// Minimum euclidean distance between any two ports.
+    pub min_port_spacing: f32,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            width: 60,
+            height: 30,
+            land_ratio: 0.4,
+            open_ports: 3,
+            closed_ports: 6,
+            min_port_spacing: 6.,
+        }
+    }
+}
+
+/// Creates a random number generator from the given seed.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (seed >> (8 * (i % 8))) as u8;
+    }
+    StdRng::from_seed(bytes)
+}
+
+/// Generates a map from the given seed.
+pub fn generate(settings: &GeneratorSettings, seed: u64) -> Result<MapData, MapError> {
+    let mut map = generate_from_rng(settings, &mut seeded_rng(seed))?;
+    map.metadata.title = format!("Archipelago #{}", seed);
+    Ok(map)
+}
+
+/// Generates a world from the given seed, including the port colors.
+pub fn generate_world(settings: &GeneratorSettings, seed: u64) -> Result<World, MapError> {
+    let mut rng = seeded_rng(seed);
+    generate_from_rng(settings, &mut rng)?.into_world(&mut rng)
+}
+
+/// Generates a map using the given random number generator, retrying until
+/// all ports can be placed and reach each other.
+pub fn generate_from_rng<R: Rng>(
+    settings: &GeneratorSettings,
+    rng: &mut R,
+) -> Result<MapData, MapError> {
+    if settings.width == 0 || settings.height == 0 {
+        return Err(MapError::Generation("map size must be non-zero".to_owned()));
+    }
+    if settings.land_ratio < 0. || settings.land_ratio >= 1. {
+        return Err(MapError::Generation(format!(
+            "land ratio {} not in [0, 1)",
+            settings.land_ratio
+        )));
+    }
+    if settings.open_ports > Color::values().len() {
+        return Err(MapError::TooManyOpenPorts {
+            ports: settings.open_ports,
+            colors: Color::values().len(),
+        });
+    }
+
+    for _ in 0..MAX_ATTEMPTS {
+        let tiles = generate_tiles(settings, rng);
+        if let Some(ports) = place_ports(settings, &tiles, rng) {
+            let map = MapData {
+                metadata: MapMetadata::default(),
+                width: settings.width,
+                height: settings.height,
+                tiles,
+                ports,
+                progression: None,
+            };
+            if map.check_connectivity().is_ok() {
+                return Ok(map);
+            }
+        }
+    }
+    Err(MapError::Generation(format!(
+        "failed to place {} ports after {} attempts",
+        settings.open_ports + settings.closed_ports,
+        MAX_ATTEMPTS
+    )))
+}
+
+/// Linear interpolation between a and b.
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Smooth interpolation factor with zero derivative at 0 and 1.
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3. - 2. * t)
+}
+
+/// Returns a row-major height map in [0, 1] made from octaves of value noise.
+fn noise<R: Rng>(width: u32, height: u32, rng: &mut R) -> Vec<f32> {
+    let mut values = vec![0.; (width * height) as usize];
+    let mut amplitude = 1.;
+    let mut total_amplitude = 0.;
+
+    for octave in 0..OCTAVES {
+        let cell_size = (BASE_CELL_SIZE >> octave).max(1) as f32;
+        let lattice_width = (width as f32 / cell_size).ceil() as usize + 2;
+        let lattice_height = (height as f32 / cell_size).ceil() as usize + 2;
+        let lattice = (0..lattice_width * lattice_height)
+            .map(|_| rng.gen::<f32>())
+            .collect::<Vec<_>>();
+        let at = |x: usize, y: usize| lattice[y * lattice_width + x];
+
+        for y in 0..height {
+            for x in 0..width {
+                let (fx, fy) = (x as f32 / cell_size, y as f32 / cell_size);
+                let (x0, y0) = (fx.floor() as usize, fy.floor() as usize);
+                let (tx, ty) = (smoothstep(fx.fract()), smoothstep(fy.fract()));
+                let top = lerp(at(x0, y0), at(x0 + 1, y0), tx);
+                let bottom = lerp(at(x0, y0 + 1), at(x0 + 1, y0 + 1), tx);
+                values[(y * width + x) as usize] += amplitude * lerp(top, bottom, ty);
+            }
+        }
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+    }
+
+    // Normalize and fade out towards the edges so the map is surrounded by sea.
+    for y in 0..height {
+        for x in 0..width {
+            let edge_distance = x.min(width - 1 - x).min(y).min(height - 1 - y) as f32;
+            let fade = smoothstep((edge_distance / EDGE_MARGIN).min(1.));
+            values[(y * width + x) as usize] *= fade / total_amplitude;
+        }
+    }
+    values
+}
+
+/// Generates tiles where the highest points of a noise map become land.
+fn generate_tiles<R: Rng>(settings: &GeneratorSettings, rng: &mut R) -> Vec<Tile> {
+    let heights = noise(settings.width, settings.height, rng);
+
+    // Find the height above which the requested ratio of tiles are land.
+    let mut sorted = heights.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let water_tiles = ((1. - settings.land_ratio) * sorted.len() as f32) as usize;
+    let sea_level = sorted.get(water_tiles).cloned().unwrap_or(1.);
+
+    heights
+        .iter()
+        .enumerate()
+        .map(|(i, height)| {
+            let position = Position::new(
+                (i as u32 % settings.width) as i32,
+                (i as u32 / settings.width) as i32,
+            );
+            if *height >= sea_level && settings.land_ratio > 0. {
+                Tile::new(position, TileKind::Land)
+            } else {
+                Tile::new(position, TileKind::Water)
+            }
+        }).collect()
+}
+
+/// Returns the positions of the largest body of connected water.
+fn largest_water_body(settings: &GeneratorSettings, tiles: &[Tile]) -> HashSet<Position> {
+    let index = |position: Position| {
+        if position.x < 0
+            || position.y < 0
+            || position.x as u32 >= settings.width
+            || position.y as u32 >= settings.height
+        {
+            None
+        } else {
+            Some((position.y as u32 * settings.width + position.x as u32) as usize)
+        }
+    };
+
+    let mut visited = vec![false; tiles.len()];
+    let mut largest = vec![];
+    for start in 0..tiles.len() {
+        if visited[start] || tiles[start].kind() != TileKind::Water {
+            continue;
+        }
+
+        // Flood fill the body of water containing the start tile.
+        let mut body = vec![];
+        let mut queue = VecDeque::new();
+        visited[start] = true;
+        queue.push_back(start);
+        while let Some(current) = queue.pop_front() {
+            body.push(tiles[current].position());
+            for neighbor in tiles[current].neighbors().iter() {
+                if let Some(i) = index(*neighbor) {
+                    if !visited[i] && tiles[i].kind() == TileKind::Water {
+                        visited[i] = true;
+                        queue.push_back(i);
+                    }
+                }
+            }
+        }
+        if body.len() > largest.len() {
+            largest = body;
+        }
+    }
+    largest.into_iter().collect()
+}
+
+/// Places ports on coastal land tiles next to the largest body of water,
+/// returning None if not all ports fit with the required spacing.
+fn place_ports<R: Rng>(
+    settings: &GeneratorSettings,
+    tiles: &[Tile],
+    rng: &mut R,
+) -> Option<Vec<PortDefinition>> {
+    let sea = largest_water_body(settings, tiles);
+    let mut candidates = tiles
+        .iter()
+        .filter(|tile| tile.kind() == TileKind::Land)
+        .filter(|tile| tile.neighbors().iter().any(|n| sea.contains(n)))
+        .map(|tile| tile.position())
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut candidates);
+
+    let num_ports = settings.open_ports + settings.closed_ports;
+    let mut positions: Vec<Position> = vec![];
+    for candidate in candidates {
+        if positions.len() == num_ports {
+            break;
+        }
+        if positions
+            .iter()
+            .all(|p| p.distance(candidate) >= settings.min_port_spacing)
+        {
+            positions.push(candidate);
+        }
+    }
+    if positions.len() < num_ports {
+        return None;
+    }
+
+    Some(
+        positions
+            .into_iter()
+            .enumerate()
+            .map(|(i, position)| PortDefinition {
+                name: format!("Port {}", i + 1),
+                position,
+                import: None,
+                export: None,
+                open_time: if i < settings.open_ports {
+                    Some(0)
+                } else {
+                    None
+                },
+            }).collect(),
+    )
+}
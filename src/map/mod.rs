@@ -0,0 +1,92 @@
+use ggez::{Context, GameError};
+use serde_json;
+use std::{error::Error, fmt, io, path::Path};
+
+mod data;
+pub mod generator;
+mod ppm;
+pub use self::data::{MapData, MapMetadata, PortDefinition};
+pub use self::ppm::{parse as parse_ppm, PpmImage};
+
+/// Path of the map played by default.
+pub const DEFAULT_MAP_PATH: &str = "/map.json";
+
+/// Errors which can occur while loading a map.
+#[derive(Debug)]
+pub enum MapError {
+    /// The map file could not be read.
+    Io(io::Error),
+    /// The map file is not valid JSON or does not match the map format.
+    Json(serde_json::Error),
+    /// The map data is inconsistent, such as missing tiles.
+    InvalidMap(String),
+    /// The file does not start with a supported magic number.
+    InvalidMagic(String),
+    /// The header (size or max value) is malformed.
+    InvalidHeader(String),
+    /// The pixel data is malformed.
+    InvalidPixel(String),
+    /// The file ended before all data was read.
+    UnexpectedEof,
+    /// No valid map could be generated with the given parameters.
+    Generation(String),
+    /// The map has more open ports than there are colors to assign.
+    TooManyOpenPorts { ports: usize, colors: usize },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Io(err) => write!(f, "failed to read map: {}", err),
+            MapError::Json(err) => write!(f, "failed to parse map: {}", err),
+            MapError::InvalidMap(msg) => write!(f, "invalid map: {}", msg),
+            MapError::InvalidMagic(magic) => write!(f, "unsupported map format: {}", magic),
+            MapError::InvalidHeader(msg) => write!(f, "invalid map header: {}", msg),
+            MapError::InvalidPixel(msg) => write!(f, "invalid map data: {}", msg),
+            MapError::UnexpectedEof => write!(f, "unexpected end of map file"),
+            MapError::Generation(msg) => write!(f, "failed to generate map: {}", msg),
+            MapError::TooManyOpenPorts { ports, colors } => {
+                write!(f, "map has {} open ports but only {} colors", ports, colors)
+            }
+        }
+    }
+}
+
+impl Error for MapError {}
+
+impl From<io::Error> for MapError {
+    fn from(err: io::Error) -> Self {
+        MapError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for MapError {
+    fn from(err: serde_json::Error) -> Self {
+        MapError::Json(err)
+    }
+}
+
+impl From<MapError> for GameError {
+    fn from(err: MapError) -> Self {
+        GameError::ResourceLoadError(err.to_string())
+    }
+}
+
+/// Loads a map from the virtual filesystem.
+/// JSON maps are read as is, PPM images are imported as a map with
+/// default metadata.
+pub fn load<P: AsRef<Path>>(ctx: &mut Context, path: P) -> Result<MapData, MapError> {
+    let is_ppm = path
+        .as_ref()
+        .extension()
+        .map_or(false, |extension| extension == "ppm");
+    let file = ctx
+        .filesystem
+        .open(path)
+        .map_err(|err| MapError::Io(io::Error::new(io::ErrorKind::NotFound, err.to_string())))?;
+    if is_ppm {
+        Ok(MapData::from_image(&parse_ppm(file)?))
+    } else {
+        MapData::read(file)
+    }
+}
@@ -0,0 +1,259 @@
+use std::{io::Read, str};
+
+use super::MapError;
+
+/// An RGB image decoded from a PPM file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PpmImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<(u8, u8, u8)>,
+}
+
+impl PpmImage {
+    /// Returns the width of the image in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the image in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns all pixels in row-major order, scaled to 8 bits per channel.
+    pub fn pixels(&self) -> &[(u8, u8, u8)] {
+        &self.pixels
+    }
+}
+
+/// The two PPM variants, given by the magic number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Samples written as whitespace separated decimal numbers (P3).
+    Ascii,
+    /// Samples written as raw bytes (P6).
+    Binary,
+}
+
+/// Cursor over the raw bytes of a PPM file.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Skips all whitespace and comments (from '#' to end of line).
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.bytes.get(self.index) {
+            if byte == b'#' {
+                while let Some(&byte) = self.bytes.get(self.index) {
+                    if byte == b'\n' || byte == b'\r' {
+                        break;
+                    }
+                    self.index += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.index += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Returns the next whitespace delimited token, if any.
+    fn token(&mut self) -> Option<&'a [u8]> {
+        self.skip_whitespace();
+        let start = self.index;
+        while let Some(&byte) = self.bytes.get(self.index) {
+            if byte.is_ascii_whitespace() || byte == b'#' {
+                break;
+            }
+            self.index += 1;
+        }
+        if start == self.index {
+            None
+        } else {
+            Some(&self.bytes[start..self.index])
+        }
+    }
+
+    /// Parses the next token as a number, naming the field in case of errors.
+    fn number(&mut self, field: &str) -> Result<u32, MapError> {
+        let token = self.token().ok_or(MapError::UnexpectedEof)?;
+        str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or_else(|| {
+                MapError::InvalidHeader(format!(
+                    "invalid {}: {:?}",
+                    field,
+                    String::from_utf8_lossy(token)
+                ))
+            })
+    }
+
+    /// Parses the next token as an ASCII sample.
+    fn ascii_sample(&mut self) -> Result<u32, MapError> {
+        let token = self.token().ok_or(MapError::UnexpectedEof)?;
+        str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or_else(|| {
+                MapError::InvalidPixel(format!(
+                    "invalid sample: {:?}",
+                    String::from_utf8_lossy(token)
+                ))
+            })
+    }
+
+    /// Returns the number of bytes left to parse.
+    fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.index)
+    }
+
+    /// Reads the next raw sample of the given byte width.
+    fn raw_sample(&mut self, wide: bool) -> Result<u32, MapError> {
+        let width = if wide { 2 } else { 1 };
+        let sample = self
+            .bytes
+            .get(self.index..self.index + width)
+            .ok_or(MapError::UnexpectedEof)?;
+        self.index += width;
+        Ok(sample
+            .iter()
+            .fold(0, |value, byte| (value << 8) | u32::from(*byte)))
+    }
+}
+
+/// Parses an ASCII (P3) or binary (P6) PPM image.
+pub fn parse<R: Read>(mut reader: R) -> Result<PpmImage, MapError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let mut parser = Parser {
+        bytes: &bytes,
+        index: 0,
+    };
+
+    let format = match parser.token() {
+        Some(b"P3") => Format::Ascii,
+        Some(b"P6") => Format::Binary,
+        Some(magic) => {
+            return Err(MapError::InvalidMagic(
+                String::from_utf8_lossy(magic).into_owned(),
+            ))
+        }
+        None => return Err(MapError::UnexpectedEof),
+    };
+
+    let width = parser.number("width")?;
+    let height = parser.number("height")?;
+    let max_value = parser.number("max value")?;
+    if width == 0 || height == 0 {
+        return Err(MapError::InvalidHeader(format!(
+            "invalid image size: {}x{}",
+            width, height
+        )));
+    }
+    if max_value == 0 || max_value > 65535 {
+        return Err(MapError::InvalidHeader(format!(
+            "invalid max value: {}",
+            max_value
+        )));
+    }
+
+    // Binary data starts after exactly one whitespace byte.
+    if format == Format::Binary {
+        match parser.bytes.get(parser.index) {
+            Some(byte) if byte.is_ascii_whitespace() => parser.index += 1,
+            Some(_) => {
+                return Err(MapError::InvalidHeader(
+                    "missing whitespace after max value".to_owned(),
+                ))
+            }
+            None => return Err(MapError::UnexpectedEof),
+        }
+    }
+
+    // Every sample takes at least one byte, or two if wider than 8 bits.
+    let num_pixels = width.checked_mul(height).ok_or_else(|| {
+        MapError::InvalidHeader(format!("image size too large: {}x{}", width, height))
+    })?;
+    let sample_size = if format == Format::Binary && max_value > 255 {
+        2
+    } else {
+        1
+    };
+    if u64::from(num_pixels) * 3 * sample_size > parser.remaining() as u64 {
+        return Err(MapError::InvalidHeader(format!(
+            "image size {}x{} exceeds the pixel data",
+            width, height
+        )));
+    }
+
+    // Scale all samples to 8 bits.
+    let mut next_sample = || -> Result<u8, MapError> {
+        let sample = match format {
+            Format::Ascii => parser.ascii_sample()?,
+            Format::Binary => parser.raw_sample(max_value > 255)?,
+        };
+        if sample > max_value {
+            return Err(MapError::InvalidPixel(format!(
+                "sample {} exceeds max value {}",
+                sample, max_value
+            )));
+        }
+        Ok(((sample * 255 + max_value / 2) / max_value) as u8)
+    };
+
+    let mut pixels = Vec::with_capacity(num_pixels as usize);
+    for _ in 0..num_pixels {
+        let (r, g, b) = (next_sample()?, next_sample()?, next_sample()?);
+        pixels.push((r, g, b));
+    }
+
+    Ok(PpmImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ascii_and_binary() {
+        let ascii = parse(&b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n"[..]).unwrap();
+        assert_eq!((ascii.width(), ascii.height()), (2, 1));
+        assert_eq!(ascii.pixels(), &[(255, 0, 0), (0, 0, 255)]);
+
+        let binary = parse(&b"P6 1 1 65535\n\xff\xff\x00\x00\x80\x00"[..]).unwrap();
+        assert_eq!(binary.pixels(), &[(255, 0, 128)]);
+    }
+
+    #[test]
+    fn rejects_oversized_header() {
+        match parse(&b"P6 4294967295 4294967295 255\n\x00\x00\x00"[..]) {
+            Err(MapError::InvalidHeader(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match parse(&b"P3 100 100 255\n0 0 0"[..]) {
+            Err(MapError::InvalidHeader(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_samples() {
+        match parse(&b"P3 1 1 255\n0 x 0\n"[..]) {
+            Err(MapError::InvalidPixel(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match parse(&b"P3 1 1 15\n0 16 0\n"[..]) {
+            Err(MapError::InvalidPixel(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
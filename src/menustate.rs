@@ -1,17 +1,42 @@
 use ggez::{
-    event, graphics, timer, {Context, GameResult},
+    event::{self, Keycode, Mod},
+    graphics, {Context, GameResult},
 };
+use std::{cell::RefCell, rc::Rc};
 
 use audio::{AudioHandler, SoundEffect};
-use config::Config;
+use config::{Config, Difficulty, GameMode};
+use editorstate::MapEditorState;
 use fonts::FontCache;
+use gamestate::GameState;
+use highscore::HighScores;
+use save;
+use scene::{Scene, Transition};
+
+/// The menu entries which can be chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuChoice {
+    StartGame,
+    Difficulty,
+    GameMode,
+    ResumeGame,
+    HighScores,
+    MapEditor,
+    Quit,
+}
 
 /// Handles and displays the main menu.
 pub struct MenuState {
     font_cache: FontCache,
     audio_handler: AudioHandler,
     config: Config,
-    frames: usize,
+    choice: Option<MenuChoice>,
+    has_saved_game: bool,
+    high_scores: HighScores,
+    is_showing_high_scores: bool,
+    /// Message shown below the menu, such as why the saved game could not
+    /// be resumed, also set by game sessions when returning to the menu.
+    notice: Rc<RefCell<Option<String>>>,
 }
 
 impl MenuState {
@@ -24,16 +49,145 @@ impl MenuState {
             font_cache: FontCache::new(ctx),
             audio_handler,
             config,
-            frames: 0,
+            choice: None,
+            has_saved_game: save::exists(ctx),
+            high_scores: HighScores::load(ctx).unwrap_or_else(|err| {
+                println!("Failed to load high scores: {}", err);
+                HighScores::default()
+            }),
+            is_showing_high_scores: false,
+            notice: Rc::new(RefCell::new(None)),
         };
         Ok(state)
     }
+
+    /// Switches to the next difficulty, custom using the settings given on
+    /// the command line.
+    fn cycle_difficulty(&mut self) {
+        let difficulties = Difficulty::values();
+        let index = difficulties
+            .iter()
+            .position(|difficulty| *difficulty == self.config.difficulty)
+            .map_or(0, |index| (index + 1) % difficulties.len());
+        self.config.difficulty = difficulties[index];
+    }
+
+    /// Switches to the next game mode, with its default parameters.
+    fn cycle_game_mode(&mut self) {
+        let modes = GameMode::values();
+        let index = modes
+            .iter()
+            .position(|mode| mode.name() == self.config.game_mode.name())
+            .map_or(0, |index| (index + 1) % modes.len());
+        self.config.game_mode = modes[index];
+    }
+
+    /// Resumes the saved game. A save which can not be loaded, such as one
+    /// from an older version, is removed and the reason shown in the menu.
+    fn resume_game(&mut self, ctx: &mut Context) -> GameResult<Transition> {
+        match save::load(ctx) {
+            Ok(simulation) => Ok(Transition::Push(Box::new(GameState::resume(
+                ctx,
+                self.config,
+                simulation,
+                self.notice.clone(),
+            )?))),
+            Err(err) => {
+                if let Err(err) = save::remove(ctx) {
+                    println!("Failed to remove saved game: {}", err);
+                }
+                self.has_saved_game = false;
+                *self.notice.borrow_mut() = Some(format!("Could not resume game: {}", err));
+                Ok(Transition::None)
+            }
+        }
+    }
+
+    /// Returns all menu buttons with their text and position on screen.
+    fn buttons(
+        &self,
+        ctx: &mut Context,
+    ) -> GameResult<Vec<(MenuChoice, graphics::Text, graphics::Point2)>> {
+        let (window_width, window_height) = graphics::get_drawable_size(ctx);
+
+        let title = graphics::Text::new(ctx, "hansa.", self.font_cache.large())?;
+        let title_y_offset = window_height as f32 * 0.2;
+        let mut y_offset = title_y_offset + (title_y_offset * 0.2).max(title.height() as f32 * 1.2);
+
+        let difficulty_label = format!("Difficulty: {}", self.config.difficulty.label());
+        let mode_label = format!("Mode: {}", self.config.game_mode.label());
+        let mut entries = vec![
+            (MenuChoice::StartGame, "Start new game"),
+            (MenuChoice::Difficulty, difficulty_label.as_str()),
+            (MenuChoice::GameMode, mode_label.as_str()),
+        ];
+        if self.has_saved_game {
+            entries.push((MenuChoice::ResumeGame, "Resume game"));
+        }
+        entries.push((MenuChoice::HighScores, "High scores"));
+        entries.push((MenuChoice::MapEditor, "Map editor"));
+        entries.push((MenuChoice::Quit, "Quit"));
+
+        let mut buttons = vec![];
+        for (choice, label) in &entries {
+            let text = graphics::Text::new(ctx, label, self.font_cache.medium())?;
+            let x_offset = (window_width - text.width()) as f32 / 2.;
+            let height = text.height() as f32;
+            buttons.push((*choice, text, graphics::Point2::new(x_offset, y_offset)));
+            y_offset += height * 1.5;
+        }
+        Ok(buttons)
+    }
 }
 
-impl event::EventHandler for MenuState {
-    /// Updates the menu state.
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        Ok(())
+impl Scene for MenuState {
+    /// Updates the menu state, leaving for the chosen entry.
+    fn update(&mut self, ctx: &mut Context) -> GameResult<Transition> {
+        let transition = match self.choice.take() {
+            Some(MenuChoice::StartGame) => Transition::Push(Box::new(GameState::new(
+                ctx,
+                self.config,
+                self.notice.clone(),
+            )?)),
+            Some(MenuChoice::ResumeGame) => self.resume_game(ctx)?,
+            Some(MenuChoice::MapEditor) => {
+                Transition::Push(Box::new(MapEditorState::new(ctx, self.config)?))
+            }
+            Some(MenuChoice::Quit) => Transition::Quit,
+            Some(MenuChoice::HighScores)
+            | Some(MenuChoice::Difficulty)
+            | Some(MenuChoice::GameMode)
+            | None => Transition::None,
+        };
+        Ok(transition)
+    }
+
+    /// Refreshes the saved game and high scores when returning to the menu.
+    fn resumed(&mut self, ctx: &mut Context) {
+        self.has_saved_game = save::exists(ctx);
+        if let Ok(high_scores) = HighScores::load(ctx) {
+            self.high_scores = high_scores;
+        }
+    }
+
+    /// Handle key presses, leaving the high-score table or quitting on escape.
+    fn key_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        keycode: Keycode,
+        _keymod: Mod,
+        _repeat: bool,
+    ) {
+        if keycode == Keycode::Escape {
+            if self.notice.borrow_mut().take().is_some() {
+                return;
+            }
+            if self.is_showing_high_scores {
+                self.is_showing_high_scores = false;
+            } else {
+                self.choice = Some(MenuChoice::Quit);
+            }
+        }
     }
 
     /// Handle mouse down events.
@@ -44,39 +198,40 @@ impl event::EventHandler for MenuState {
         x: i32,
         y: i32,
     ) {
-        let (window_width, window_height) = graphics::get_drawable_size(ctx);
-
-        // Set blackish color.
-        let title = graphics::Text::new(ctx, "hansa.", self.font_cache.large()).unwrap();
-        let title_y_offset = window_height as f32 * 0.2;
+        // Any click dismisses the notice and leaves the high-score table.
+        *self.notice.borrow_mut() = None;
+        if self.is_showing_high_scores {
+            self.audio_handler.play(SoundEffect::ClickUIButton);
+            self.is_showing_high_scores = false;
+            return;
+        }
 
-        let start_game =
-            graphics::Text::new(ctx, "Start new game", self.font_cache.medium()).unwrap();
-        let start_game_y_offset =
-            title_y_offset + (title_y_offset * 0.2).max(title.height() as f32 * 1.2);
-        let start_game_x_offset = (window_width - start_game.width()) as f32 / 2.;
-
-        let start_button_rect = graphics::Rect::new(
-            start_game_x_offset,
-            start_game_y_offset,
-            start_game.width() as f32,
-            start_game.height() as f32,
-        );
-        if start_button_rect.contains(graphics::Point2::new(
+        let mouse_position = graphics::Point2::new(
             self.config.scaling as f32 * x as f32,
             self.config.scaling as f32 * y as f32,
-        )) {
-            self.audio_handler.play(SoundEffect::ClickUIButton);
-            ctx.quit().expect("Failed to start game");
+        );
+        let buttons = self.buttons(ctx).expect("Failed to create menu buttons");
+        for (choice, text, position) in buttons {
+            let button_rect = graphics::Rect::new(
+                position.x,
+                position.y,
+                text.width() as f32,
+                text.height() as f32,
+            );
+            if button_rect.contains(mouse_position) {
+                self.audio_handler.play(SoundEffect::ClickUIButton);
+                match choice {
+                    MenuChoice::HighScores => self.is_showing_high_scores = true,
+                    MenuChoice::Difficulty => self.cycle_difficulty(),
+                    MenuChoice::GameMode => self.cycle_game_mode(),
+                    _ => self.choice = Some(choice),
+                }
+            }
         }
     }
 
     /// Draws the current state to the screen with the given context.
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        if self.frames % 100 == 0 {
-            println!("FPS: {:.1}", timer::get_fps(ctx));
-        }
-        graphics::clear(ctx);
         graphics::set_background_color(ctx, graphics::Color::from((243, 243, 236)));
 
         let (window_width, window_height) = graphics::get_drawable_size(ctx);
@@ -94,31 +249,34 @@ impl event::EventHandler for MenuState {
             0.,
         )?;
 
-        let start_game = graphics::Text::new(ctx, "Start new game", self.font_cache.medium())?;
-        let start_game_y_offset =
-            title_y_offset + (title_y_offset * 0.2).max(title.height() as f32 * 1.2);
-        let start_game_x_offset = (window_width - start_game.width()) as f32 / 2.;
+        if self.is_showing_high_scores {
+            let y_offset = title_y_offset + title.height() as f32 * 1.2;
+            self.high_scores.paint(
+                self.font_cache.small(),
+                ctx,
+                y_offset,
+                self.config.game_mode,
+                None,
+            )?;
+        } else {
+            let mut y_offset = title_y_offset;
+            for (_, text, position) in self.buttons(ctx)? {
+                y_offset = position.y + text.height() as f32 * 1.5;
+                graphics::draw(ctx, &text, position, 0.)?;
+            }
 
-        graphics::draw(
-            ctx,
-            &start_game,
-            graphics::Point2::new(start_game_x_offset, start_game_y_offset),
-            0.,
-        )?;
+            // Show the notice in red below the buttons.
+            if let Some(notice) = &*self.notice.borrow() {
+                graphics::set_color(ctx, graphics::Color::from_rgb(232, 66, 54))?;
+                let text = graphics::Text::new(ctx, notice, self.font_cache.small())?;
+                let x_offset = (window_width as f32 - text.width() as f32).max(0.) / 2.;
+                graphics::draw(ctx, &text, graphics::Point2::new(x_offset, y_offset), 0.)?;
+            }
+        }
 
         // Reset color to default (white).
         graphics::set_color(ctx, graphics::Color::from_rgb(255, 255, 255))?;
 
-        graphics::present(ctx);
-        self.frames += 1;
-        // And yield the timeslice
-        // This tells the OS that we're done using the CPU but it should
-        // get back to this program as soon as it can.
-        // This ideally prevents the game from using 100% CPU all the time
-        // even if vsync is off.
-        // The actual behavior can be a little platform-specific.
-        timer::yield_now();
-
         Ok(())
     }
 }
@@ -0,0 +1,77 @@
+use ggez::{
+    graphics::{draw, get_drawable_size, set_color, Color, Font, Point2, Text},
+    timer::duration_to_f64,
+    Context, GameResult,
+};
+use std::time::Duration;
+
+use config::Config;
+use update::Updatable;
+
+/// Time a notification is shown: 3s.
+const NOTIFICATION_LENGTH: u64 = 3;
+
+/// Time at the end of a notification during which it fades out: 1s.
+const FADE_LENGTH: u64 = 1;
+
+/// A short message shown next to the shipyard for a while.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    text: String,
+    remaining: Duration,
+}
+
+impl Notification {
+    /// Creates a new notification with the given text.
+    pub fn new(text: String) -> Self {
+        Notification {
+            text,
+            remaining: Duration::from_secs(NOTIFICATION_LENGTH),
+        }
+    }
+
+    /// Returns true if the notification is no longer shown.
+    pub fn has_expired(&self) -> bool {
+        self.remaining == Duration::from_secs(0)
+    }
+
+    /// Draws the notification on screen, next to the shipyard.
+    /// Does not implement Drawable since it is unable to be drawn using a
+    /// spritebatch.
+    pub fn paint(&self, font: &Font, ctx: &mut Context, config: &Config) -> GameResult<()> {
+        let (window_width, _) = get_drawable_size(ctx);
+        let cell_size = (config.scaling * window_width) as f32 / config.grid_width as f32;
+
+        let x_offset = cell_size * (config.grid_width / 2 + 9) as f32;
+        let y_offset = cell_size * (config.grid_height as f32 + 2.5);
+
+        // Fade out at the end.
+        let alpha = (duration_to_f64(self.remaining) as f32 / FADE_LENGTH as f32).min(1.);
+
+        // Set blackish color.
+        set_color(ctx, Color::new(69. / 255., 55. / 255., 52. / 255., alpha))?;
+        let text = Text::new(ctx, &self.text, font)?;
+        draw(
+            ctx,
+            &text,
+            Point2::new(x_offset, y_offset - text.height() as f32 / 2.),
+            0.,
+        )?;
+        // Reset color to default (white).
+        set_color(ctx, Color::from_rgb(255, 255, 255))?;
+
+        Ok(())
+    }
+}
+
+impl<'a> Updatable<'a> for Notification {
+    type Data = ();
+
+    /// Counts down the time the notification is shown.
+    fn update(&mut self, delta: Duration, _data: ()) {
+        self.remaining = self
+            .remaining
+            .checked_sub(delta)
+            .unwrap_or(Duration::from_secs(0));
+    }
+}
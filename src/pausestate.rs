@@ -0,0 +1,155 @@
+use ggez::{
+    event::{self, Keycode, Mod},
+    graphics, {Context, GameResult},
+};
+use std::{cell::Cell, rc::Rc};
+
+use audio::{AudioHandler, SoundEffect};
+use config::Config;
+use fonts::FontCache;
+use scene::{Scene, Transition};
+
+/// The pause menu entries which can be chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseChoice {
+    Resume,
+    Restart,
+    QuitToMenu,
+}
+
+/// Overlay shown on top of a paused game session.
+pub struct PauseState {
+    font_cache: FontCache,
+    audio_handler: AudioHandler,
+    config: Config,
+    /// Choice made by the player, read by the paused game when resumed.
+    choice: Rc<Cell<Option<PauseChoice>>>,
+}
+
+impl PauseState {
+    /// Creates a new pause overlay, which stores the choice made in the
+    /// given cell.
+    pub fn new(
+        ctx: &mut Context,
+        config: Config,
+        choice: Rc<Cell<Option<PauseChoice>>>,
+    ) -> GameResult<Self> {
+        let state = PauseState {
+            font_cache: FontCache::new(ctx),
+            audio_handler: AudioHandler::new(ctx),
+            config,
+            choice,
+        };
+        Ok(state)
+    }
+
+    /// Returns all menu buttons with their text and position on screen.
+    fn buttons(
+        &self,
+        ctx: &mut Context,
+    ) -> GameResult<Vec<(PauseChoice, graphics::Text, graphics::Point2)>> {
+        let (window_width, window_height) = graphics::get_drawable_size(ctx);
+
+        let title = graphics::Text::new(ctx, "Paused.", self.font_cache.medium())?;
+        let title_y_offset = window_height as f32 * 0.3;
+        let mut y_offset = title_y_offset + title.height() as f32 * 2.;
+
+        let mut buttons = vec![];
+        for (choice, label) in &[
+            (PauseChoice::Resume, "Resume"),
+            (PauseChoice::Restart, "Restart"),
+            (PauseChoice::QuitToMenu, "Quit to menu"),
+        ] {
+            let text = graphics::Text::new(ctx, label, self.font_cache.medium())?;
+            let x_offset = (window_width - text.width()) as f32 / 2.;
+            let height = text.height() as f32;
+            buttons.push((*choice, text, graphics::Point2::new(x_offset, y_offset)));
+            y_offset += height * 1.5;
+        }
+        Ok(buttons)
+    }
+}
+
+impl Scene for PauseState {
+    /// Updates the pause overlay, returning to the game once a choice is made.
+    fn update(&mut self, _ctx: &mut Context) -> GameResult<Transition> {
+        if self.choice.get().is_some() {
+            Ok(Transition::Pop)
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    /// The paused game is drawn below.
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    /// Handle key presses, resuming on escape.
+    fn key_down_event(&mut self, _ctx: &mut Context, keycode: Keycode, _keymod: Mod, repeat: bool) {
+        if keycode == Keycode::Escape && !repeat {
+            self.choice.set(Some(PauseChoice::Resume));
+        }
+    }
+
+    /// Handle mouse down events.
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        _button: event::MouseButton,
+        x: i32,
+        y: i32,
+    ) {
+        let mouse_position = graphics::Point2::new(
+            self.config.scaling as f32 * x as f32,
+            self.config.scaling as f32 * y as f32,
+        );
+        let buttons = self.buttons(ctx).expect("Failed to create pause buttons");
+        for (choice, text, position) in buttons {
+            let button_rect = graphics::Rect::new(
+                position.x,
+                position.y,
+                text.width() as f32,
+                text.height() as f32,
+            );
+            if button_rect.contains(mouse_position) {
+                self.audio_handler.play(SoundEffect::ClickUIButton);
+                self.choice.set(Some(choice));
+            }
+        }
+    }
+
+    /// Draws the overlay on top of the paused game.
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let (window_width, window_height) = graphics::get_drawable_size(ctx);
+
+        // Fade out the game below.
+        graphics::set_color(ctx, graphics::Color::from_rgba(243, 243, 236, 200))?;
+        graphics::rectangle(
+            ctx,
+            graphics::DrawMode::Fill,
+            graphics::Rect::new(0., 0., window_width as f32, window_height as f32),
+        )?;
+
+        // Set blackish color.
+        graphics::set_color(ctx, graphics::Color::from_rgb(69, 55, 52))?;
+        let title = graphics::Text::new(ctx, "Paused.", self.font_cache.medium())?;
+        let title_y_offset = window_height as f32 * 0.3;
+        let title_x_offset = (window_width - title.width()) as f32 / 2.;
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::Point2::new(title_x_offset, title_y_offset),
+            0.,
+        )?;
+
+        for (_, text, position) in self.buttons(ctx)? {
+            graphics::draw(ctx, &text, position, 0.)?;
+        }
+
+        // Reset color to default (white).
+        graphics::set_color(ctx, graphics::Color::from_rgb(255, 255, 255))?;
+
+        Ok(())
+    }
+}
@@ -1,15 +1,43 @@
-use ggez::graphics::{Color as ggezColor, DrawParam, Point2, Rect};
+use ggez::{
+    graphics::{Color as ggezColor, DrawParam, Point2, Rect},
+    timer::duration_to_f64,
+};
 use rand::{seq::sample_slice, Rng};
+use std::time::Duration;
 
 use animation::Animation;
 use color::Color;
 use draw::Drawable;
 use geometry::Position;
+use update::Updatable;
 use world::World;
 
-/// Returns whether the given amount of ports is a valid configuration of imports/exports.
-pub fn is_valid_arrangement(ports: &[Port]) -> bool {
-    let (mut imports, mut exports) = (Color::values(), Color::values());
+/// Time between each unit added to the export stockpile of ports on a route: 5s.
+const STOCK_INTERVAL: u64 = 5;
+
+/// Time between each unit added to the import demand of ports on a route: 5s.
+const DEMAND_INTERVAL: u64 = 5;
+
+/// Units of stock and demand a port starts with.
+const INITIAL_UNITS: u32 = 2;
+
+/// Units of stock or demand at which a port is overloaded.
+const OVERLOAD_UNITS: u32 = 8;
+
+/// Time a port may stay overloaded before the game is over: 20s.
+const OVERLOAD_TIME: u64 = 20;
+
+/// Factor by which the stock and demand of ports on no route grow slower,
+/// giving time to connect newly opened ports.
+const UNCONNECTED_SLOWDOWN: u32 = 4;
+
+/// Size of the stock and demand pips relative to a tile.
+const PIP_SCALE: f32 = 0.2;
+
+/// Returns whether the given amount of ports is a valid configuration of
+/// imports/exports, trading all of the given colors.
+pub fn is_valid_arrangement(ports: &[Port], colors: &[Color]) -> bool {
+    let (mut imports, mut exports) = (colors.to_vec(), colors.to_vec());
     for port in ports {
         let (import, export) = (port.import(), port.export());
 
@@ -27,11 +55,21 @@ pub fn is_valid_arrangement(ports: &[Port]) -> bool {
     imports.is_empty() && exports.is_empty()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Port {
     import: Color,
     export: Color,
     position: Position,
+    /// Units of the export color waiting to be loaded.
+    stock: u32,
+    /// Units of the import color waiting to be delivered.
+    demand: u32,
+    /// Time since a unit was last added to the stockpile.
+    since_stock: Duration,
+    /// Time since a unit was last added to the demand.
+    since_demand: Duration,
+    /// Time spent overloaded, counting back down once no longer overloaded.
+    overload: Duration,
     animation: Option<Animation>,
 }
 
@@ -42,6 +80,11 @@ impl Port {
             position,
             import,
             export,
+            stock: INITIAL_UNITS,
+            demand: INITIAL_UNITS,
+            since_stock: Duration::from_secs(0),
+            since_demand: Duration::from_secs(0),
+            overload: Duration::from_secs(0),
             animation: None,
         }
     }
@@ -71,12 +114,59 @@ impl Port {
         &mut self.export
     }
 
-    /// Samples a random import and export color.
-    pub fn sample_colors<R: Rng>(gen: &mut R) -> (Color, Color) {
-        let colors = sample_slice(gen, &Color::values(), 2);
+    /// Returns the units of the export color waiting to be loaded.
+    pub fn stock(&self) -> u32 {
+        self.stock
+    }
+
+    /// Returns the units of the import color waiting to be delivered.
+    pub fn demand(&self) -> u32 {
+        self.demand
+    }
+
+    /// Takes at most the given units from the stockpile, returning the
+    /// amount taken.
+    pub fn take_stock(&mut self, amount: u32) -> u32 {
+        let taken = amount.min(self.stock);
+        self.stock -= taken;
+        taken
+    }
+
+    /// Meets at most the given units of the demand, returning the amount
+    /// met.
+    pub fn meet_demand(&mut self, amount: u32) -> u32 {
+        let met = amount.min(self.demand);
+        self.demand -= met;
+        met
+    }
+
+    /// Returns true if either the stockpile or the demand is full.
+    pub fn is_overloaded(&self) -> bool {
+        self.stock >= OVERLOAD_UNITS || self.demand >= OVERLOAD_UNITS
+    }
+
+    /// Returns true if the port has stayed overloaded for too long.
+    pub fn has_overflowed(&self) -> bool {
+        self.overload >= Duration::from_secs(OVERLOAD_TIME)
+    }
+
+    /// Samples a random import and export color out of the given colors.
+    pub fn sample_colors<R: Rng>(gen: &mut R, colors: &[Color]) -> (Color, Color) {
+        let colors = sample_slice(gen, colors, 2);
         (colors[0], colors[1])
     }
 
+    /// Samples a random color out of the given colors other than the given
+    /// one, which there must be.
+    pub fn sample_other_color<R: Rng>(gen: &mut R, colors: &[Color], other: Color) -> Color {
+        let others = colors
+            .iter()
+            .cloned()
+            .filter(|color| *color != other)
+            .collect::<Vec<_>>();
+        sample_slice(gen, &others, 1)[0]
+    }
+
     pub fn animation_mut(&mut self) -> &mut Option<Animation> {
         &mut self.animation
     }
@@ -92,7 +182,23 @@ impl<'a> Drawable<'a> for Port {
     fn draw(&self, _world: &World) -> Vec<DrawParam> {
         let (e_r, e_g, e_b) = self.export.rgb();
         let (i_r, i_g, i_b) = self.import.rgb();
-        vec![
+        let mut params = vec![];
+
+        // Show the overload countdown as a dark ring growing behind the port.
+        if self.overload > Duration::from_secs(0) {
+            let progress = duration_to_f64(self.overload) as f32 / OVERLOAD_TIME as f32;
+            let scale = 1. + progress.min(1.);
+            params.push(DrawParam {
+                src: Rect::new(0., 2. * Self::TILE_OFFSET, Self::TILE_SIZE, Self::TILE_SIZE),
+                dest: Point2::new(self.position.x as f32 + 0.5, self.position.y as f32 + 0.5),
+                color: Some(ggezColor::from_rgba(69, 55, 52, 120)),
+                offset: Point2::new(0.5, 0.5),
+                scale: Point2::new(scale, scale),
+                ..Default::default()
+            });
+        }
+
+        params.extend(vec![
             DrawParam {
                 src: Rect::new(
                     Self::TILE_OFFSET,
@@ -112,6 +218,63 @@ impl<'a> Drawable<'a> for Port {
                 offset: Point2::new(0.5, 0.5),
                 ..Default::default()
             },
-        ]
+        ]);
+
+        // Draw waiting stock above the port and unmet demand below it.
+        for (units, (r, g, b), y_offset) in &[
+            (self.stock, (e_r, e_g, e_b), -0.1),
+            (self.demand, (i_r, i_g, i_b), 1.1),
+        ] {
+            let width = *units as f32 * PIP_SCALE;
+            for index in 0..*units {
+                params.push(DrawParam {
+                    src: Rect::new(0., 2. * Self::TILE_OFFSET, Self::TILE_SIZE, Self::TILE_SIZE),
+                    dest: Point2::new(
+                        self.position.x as f32 + 0.5 - width / 2.
+                            + (index as f32 + 0.5) * PIP_SCALE,
+                        self.position.y as f32 + y_offset,
+                    ),
+                    color: Some(ggezColor::from_rgb(*r, *g, *b)),
+                    offset: Point2::new(0.5, 0.5),
+                    scale: Point2::new(PIP_SCALE, PIP_SCALE),
+                    ..Default::default()
+                });
+            }
+        }
+        params
+    }
+}
+
+impl<'a> Updatable<'a> for Port {
+    /// Whether the port is on any route.
+    type Data = bool;
+
+    /// Grows the stockpile and demand over time, slower while the port is
+    /// not on any route, and counts the time spent overloaded.
+    fn update(&mut self, delta: Duration, connected: bool) {
+        let slowdown = if connected { 1 } else { UNCONNECTED_SLOWDOWN };
+        let stock_interval = Duration::from_secs(STOCK_INTERVAL) * slowdown;
+        let demand_interval = Duration::from_secs(DEMAND_INTERVAL) * slowdown;
+
+        self.since_stock += delta;
+        if self.since_stock >= stock_interval {
+            self.since_stock -= stock_interval;
+            self.stock = (self.stock + 1).min(OVERLOAD_UNITS);
+        }
+        self.since_demand += delta;
+        if self.since_demand >= demand_interval {
+            self.since_demand -= demand_interval;
+            self.demand = (self.demand + 1).min(OVERLOAD_UNITS);
+        }
+
+        // Recover at the same pace as overloading.
+        if self.is_overloaded() {
+            self.overload += delta;
+        } else {
+            self.overload = self
+                .overload
+                .checked_sub(delta)
+                .unwrap_or(Duration::from_secs(0));
+        }
     }
 }
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, time::Duration};
+
+use color::Color;
+use config::{Difficulty, DifficultySettings};
+use geometry::Position;
+use route::DEFAULT_LINES;
+
+/// Time between the progression steps of maps without their own progression,
+/// at normal difficulty.
+const DEFAULT_INTERVAL: u64 = 15;
+
+/// Condition under which a progression event happens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Once, the given number of seconds into the session.
+    Time(u64),
+    /// Once, when the score reaches the given value.
+    Score(u32),
+    /// Once, when the given number of ports are open.
+    OpenPorts(usize),
+    /// Repeatedly, every given number of seconds.
+    Interval(u64),
+}
+
+/// Change made to the game session by a progression event.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Opens the closed port at the given position.
+    OpenPort(Position),
+    /// Opens a random closed port, changing the colors of a random port
+    /// instead if all ports are open.
+    OpenRandomPort,
+    /// Changes the colors of the port at the given position, or of a
+    /// random port if not given.
+    ChangePortColors(Option<Position>),
+    /// Grants the given number of ships to the shipyard.
+    GrantShips(usize),
+    /// Makes the given number of further route lines available.
+    GrantLines(usize),
+    /// Adds the given color to the colors being traded.
+    AddColor(Color),
+}
+
+/// A set of actions taken when the trigger is met.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProgressionEvent {
+    pub trigger: Trigger,
+    pub actions: Vec<Action>,
+}
+
+/// Ships granted by the shipyard when the trigger is met.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShipGrant {
+    pub trigger: Trigger,
+    pub ships: usize,
+}
+
+/// Route lines made available when the trigger is met.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineGrant {
+    pub trigger: Trigger,
+    pub lines: usize,
+}
+
+/// Returns the number of lines available from the start of maps without
+/// their own.
+fn default_lines() -> usize {
+    DEFAULT_LINES
+}
+
+/// Returns the ship grants of maps without their own, granting ships
+/// earlier on easier difficulties.
+fn default_ship_grants() -> HashMap<Difficulty, Vec<ShipGrant>> {
+    let grant = |trigger, ships| ShipGrant { trigger, ships };
+    vec![
+        (
+            Difficulty::Easy,
+            vec![
+                grant(Trigger::Time(60), 1),
+                grant(Trigger::Score(3), 1),
+                grant(Trigger::OpenPorts(5), 1),
+                grant(Trigger::Time(180), 1),
+            ],
+        ),
+        (
+            Difficulty::Normal,
+            vec![
+                grant(Trigger::Score(5), 1),
+                grant(Trigger::OpenPorts(6), 1),
+                grant(Trigger::Time(180), 1),
+            ],
+        ),
+        (
+            Difficulty::Hard,
+            vec![
+                grant(Trigger::Score(10), 1),
+                grant(Trigger::OpenPorts(8), 1),
+            ],
+        ),
+    ].into_iter()
+    .collect()
+}
+
+/// Returns the line grants of maps without their own, unlocking lines
+/// earlier on easier difficulties.
+fn default_line_grants() -> HashMap<Difficulty, Vec<LineGrant>> {
+    let grant = |trigger, lines| LineGrant { trigger, lines };
+    vec![
+        (
+            Difficulty::Easy,
+            vec![grant(Trigger::Score(5), 1), grant(Trigger::Time(240), 1)],
+        ),
+        (
+            Difficulty::Normal,
+            vec![
+                grant(Trigger::Score(10), 1),
+                grant(Trigger::OpenPorts(8), 1),
+            ],
+        ),
+        (Difficulty::Hard, vec![grant(Trigger::Score(15), 1)]),
+    ].into_iter()
+    .collect()
+}
+
+/// Pacing of a game session, given as the colors traded from the start and
+/// the events changing the session as it goes on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Progression {
+    /// Colors traded from the start, all colors if omitted.
+    #[serde(default = "Color::values")]
+    pub colors: Vec<Color>,
+    pub events: Vec<ProgressionEvent>,
+    /// Ships granted on milestones for each difficulty, the default grants
+    /// if omitted.
+    #[serde(default = "default_ship_grants")]
+    pub ship_grants: HashMap<Difficulty, Vec<ShipGrant>>,
+    /// Number of route lines available from the start.
+    #[serde(default = "default_lines")]
+    pub lines: usize,
+    /// Route lines made available on milestones for each difficulty, the
+    /// default grants if omitted.
+    #[serde(default = "default_line_grants")]
+    pub line_grants: HashMap<Difficulty, Vec<LineGrant>>,
+}
+
+impl Progression {
+    /// Returns the progression as played at the given difficulty, with the
+    /// ship and line grants of the difficulty added as events. Intervals are
+    /// scaled by the port interval of the settings, and only the number of
+    /// colors of the settings are traded from the start. Custom difficulties
+    /// are granted ships and lines as on normal difficulty.
+    pub fn for_difficulty(mut self, difficulty: Difficulty, settings: &DifficultySettings) -> Self {
+        for event in &mut self.events {
+            if let Trigger::Interval(interval) = event.trigger {
+                let scaled = interval * settings.port_interval / DEFAULT_INTERVAL;
+                event.trigger = Trigger::Interval(scaled.max(1));
+            }
+        }
+        self.colors.truncate(settings.colors.max(2));
+
+        let difficulty = match difficulty {
+            Difficulty::Custom => Difficulty::Normal,
+            _ => difficulty,
+        };
+        let grants = self.ship_grants.remove(&difficulty).unwrap_or_default();
+        self.events
+            .extend(grants.into_iter().map(|grant| ProgressionEvent {
+                trigger: grant.trigger,
+                actions: vec![Action::GrantShips(grant.ships)],
+            }));
+        self.ship_grants.clear();
+        let grants = self.line_grants.remove(&difficulty).unwrap_or_default();
+        self.events
+            .extend(grants.into_iter().map(|grant| ProgressionEvent {
+                trigger: grant.trigger,
+                actions: vec![Action::GrantLines(grant.lines)],
+            }));
+        self.line_grants.clear();
+        self
+    }
+}
+
+impl Default for Progression {
+    /// Opens a new port, or changes port colors if all are open, at a
+    /// fixed interval with all colors traded.
+    fn default() -> Self {
+        Progression {
+            colors: Color::values(),
+            events: vec![ProgressionEvent {
+                trigger: Trigger::Interval(DEFAULT_INTERVAL),
+                actions: vec![Action::OpenRandomPort],
+            }],
+            ship_grants: default_ship_grants(),
+            lines: DEFAULT_LINES,
+            line_grants: default_line_grants(),
+        }
+    }
+}
+
+/// Keeps track of which events of a progression have happened.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProgressionState {
+    /// Time each event last happened, if it has.
+    last_happened: Vec<Option<Duration>>,
+}
+
+impl ProgressionState {
+    /// Creates a new state where no event of the progression has happened.
+    pub fn new(progression: &Progression) -> Self {
+        ProgressionState {
+            last_happened: vec![None; progression.events.len()],
+        }
+    }
+
+    /// Returns all events of the progression due at the given time, score
+    /// and number of open ports, marking them as happened.
+    pub fn advance<'a>(
+        &mut self,
+        progression: &'a Progression,
+        elapsed: Duration,
+        score: u32,
+        open_ports: usize,
+    ) -> Vec<&'a ProgressionEvent> {
+        let mut due = vec![];
+        for (event, last_happened) in progression.events.iter().zip(&mut self.last_happened) {
+            let is_due = match (event.trigger, *last_happened) {
+                (Trigger::Time(time), None) => elapsed >= Duration::from_secs(time),
+                (Trigger::Score(target), None) => score >= target,
+                (Trigger::OpenPorts(target), None) => open_ports >= target,
+                (Trigger::Interval(interval), last) => {
+                    let since = last.unwrap_or_else(|| Duration::from_secs(0));
+                    elapsed >= since + Duration::from_secs(interval)
+                }
+                _ => false,
+            };
+            if is_due {
+                *last_happened = Some(elapsed);
+                due.push(event);
+            }
+        }
+        due
+    }
+}
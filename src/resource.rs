@@ -0,0 +1,23 @@
+use serde::de::DeserializeOwned;
+use serde_json;
+use std::{env, fs::File, path::PathBuf};
+
+/// Returns the resources folder, in the crate when run through cargo and
+/// otherwise next to the executable.
+fn resource_dir() -> PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        return PathBuf::from(manifest_dir).join("resources");
+    }
+    let mut path = env::current_exe().expect("Failed to locate executable");
+    path.pop();
+    path.join("resources")
+}
+
+/// Reads a list of definitions from the JSON file of the given name in the
+/// resources folder. Panics if the file is missing or malformed, since the
+/// game cannot run without its definitions.
+pub fn load_list<T: DeserializeOwned>(name: &str) -> Vec<T> {
+    let path = resource_dir().join(name);
+    let file = File::open(&path).unwrap_or_else(|e| panic!("Failed to open {:?}: {}", path, e));
+    serde_json::from_reader(file).unwrap_or_else(|e| panic!("Failed to parse {:?}: {}", path, e))
+}
@@ -1,7 +1,8 @@
 use ggez::graphics::{Color as ggezColor, DrawParam, Point2, Rect};
 use std::{
-    collections::{BinaryHeap, HashMap},
+    collections::{BinaryHeap, HashMap, HashSet},
     i32::MAX,
+    mem,
 };
 
 use config::Config;
@@ -12,36 +13,32 @@ use ship::Ship;
 use tile::{Tile, TileKind};
 use world::World;
 
+mod navigation;
 mod routebuilder;
 mod shape;
 mod waypoint;
+pub use self::navigation::NavigationGrid;
 pub use self::routebuilder::RouteBuilder;
-pub use self::shape::{RouteShape, ShapeSelector};
+pub use self::shape::{RouteShape, ShapeSelector, DEFAULT_LINES};
 pub use self::waypoint::Waypoint;
 
-/// Returns all reachable tiles from a given position which a trade
-/// route can pass through.
-pub fn reachable(map: &[Tile], ports: &[Port], position: Position) -> Vec<Position> {
-    map.iter()
-        .filter(move |tile| {
-            let other_position = tile.position();
-            position.distance(other_position) <= 1.
-                && (tile.kind() == TileKind::Water
-                    || ports.iter().any(|p| p.position() == other_position))
-        }).map(|tile| tile.position())
-        .collect()
+/// Returns the manhattan distance between two positions, which never
+/// overestimates the cost of a path since every step costs at least one.
+fn heuristic(position: Position, goal: Position) -> i32 {
+    (position.x - goal.x).abs() + (position.y - goal.y).abs()
 }
 
 /// Finds the shortest path from start to goal using astar with
 /// manhattan distance heuristic.
+/// Steps from positions already occupied by a waypoint cost extra to avoid
+/// overlapping routes if possible.
 pub fn find_path(
-    map: &[Tile],
-    ports: &[Port],
-    waypoints: &[Waypoint],
+    grid: &NavigationGrid,
+    occupied: &HashSet<Waypoint>,
     start: Position,
     goal: Position,
 ) -> Option<(i32, Vec<Position>)> {
-    // Node -> steps, cost mapping.
+    // Node -> lowest known cost mapping.
     let mut dist = HashMap::<Position, i32>::new();
     let mut frontier = BinaryHeap::new();
     let mut previous = HashMap::<Position, Position>::new();
@@ -49,45 +46,47 @@ pub fn find_path(
     // We're at `start`, with a zero cost
     dist.insert(start, 0);
     frontier.push(OrdPosition {
-        weight: 0,
+        weight: heuristic(start, goal),
         position: start,
     });
 
     let mut cost = None;
-    // Examine the frontier with lower cost nodes first (min-heap)
+    // Examine the frontier with lowest estimated total cost first (min-heap)
     while let Some(OrdPosition { position, weight }) = frontier.pop() {
+        let steps = weight - heuristic(position, goal);
+
         // Alternatively we could have continued to find all shortest paths
         if position == goal {
-            cost = Some(weight);
+            cost = Some(steps);
             break;
         }
 
         // Important as we may have already found a better way
-        if weight > *dist.get(&position).unwrap_or(&MAX) {
+        if steps > *dist.get(&position).unwrap_or(&MAX) {
             continue;
         }
 
+        // Add extra weight if already path exists here to avoid overlap if possible.
+        let step_cost = if occupied.contains(&Waypoint::from(position)) {
+            2
+        } else {
+            1
+        };
+
         // For each node we can reach, see if we can find a way with
         // a lower cost going through this node
-        for neighbor in reachable(map, ports, position) {
-            // Add extra weight if already path exists here to avoid overlap if possible.
-            let next_weight = weight
-                + if waypoints.iter().any(|w| *w == Waypoint::from(position)) {
-                    2
-                } else {
-                    1
-                };
-            let next = OrdPosition {
-                position: neighbor,
-                weight: next_weight,
-            };
+        for neighbor in grid.reachable(position) {
+            let next_steps = steps + step_cost;
 
             // If so, add it to the frontier and continue
-            if next.weight < *dist.get(&next.position).unwrap_or(&MAX) {
+            if next_steps < *dist.get(&neighbor).unwrap_or(&MAX) {
                 // Relaxation, we have now found a better way
-                dist.insert(next.position, next.weight);
-                previous.insert(next.position, position);
-                frontier.push(next);
+                dist.insert(neighbor, next_steps);
+                previous.insert(neighbor, position);
+                frontier.push(OrdPosition {
+                    position: neighbor,
+                    weight: next_steps + heuristic(neighbor, goal),
+                });
             }
         }
     }
@@ -108,8 +107,38 @@ pub fn find_path(
     }
 }
 
+/// Outcome of removing a link from a route.
+#[derive(Debug)]
+pub enum LinkRemoval {
+    /// No link was removed, as the route has no other links.
+    Kept,
+    /// No link was removed, as it is in the middle and splitting the route
+    /// was not allowed.
+    Refused,
+    /// The link was removed, shortening the route or opening up its loop.
+    Removed,
+    /// The link was removed from the middle, splitting off the given route.
+    Split(Route),
+}
+
+/// Layout of a route, as its links and its ships with their cargo, from
+/// which the route can be restored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteLayout {
+    paths: Vec<(Position, Vec<Waypoint>)>,
+    ships: Vec<Ship>,
+}
+
+impl RouteLayout {
+    /// Returns an iterator over all ships on the route.
+    pub fn ships(&self) -> impl Iterator<Item = &Ship> {
+        self.ships.iter()
+    }
+}
+
 /// Represents a trading route which exists between a series of ports.
-#[derive(Debug, Clone)]
+/// A route closed into a loop ends at the port it starts from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Route {
     ships: Vec<Ship>,
     paths: Vec<(Position, Vec<Waypoint>)>,
@@ -171,7 +200,14 @@ impl Route {
         path
     }
 
+    /// Returns true if the last port of this route links back to the first.
+    pub fn is_loop(&self) -> bool {
+        self.paths.len() > 2
+            && self.paths.first().map(|(p, _)| p) == self.paths.last().map(|(p, _)| p)
+    }
+
     /// Returns the next path after the given port location, return None if last.
+    /// On a loop the first path follows the last one.
     pub fn next_path(&self, port: Position) -> Option<Vec<Waypoint>> {
         self.paths
             .iter()
@@ -180,7 +216,11 @@ impl Route {
     }
 
     /// Returns the previous path after the given port location, return None if first.
+    /// Always None on a loop, turning ships around to circulate forwards.
     pub fn previous_path(&self, port: Position) -> Option<Vec<Waypoint>> {
+        if self.is_loop() {
+            return None;
+        }
         self.paths
             .iter()
             .rev()
@@ -190,15 +230,20 @@ impl Route {
     }
 
     /// Adds a new link to this route, inserting it after start first occures.
+    /// Linking the last port to the first closes the route into a loop,
+    /// after which no more links can be added.
     pub fn add_link(
         &mut self,
-        map: &[Tile],
-        ports: &[Port],
-        waypoints: &[Waypoint],
+        grid: &NavigationGrid,
+        occupied: &HashSet<Waypoint>,
         start: Position,
         end: Position,
         path: Vec<Waypoint>,
     ) {
+        if self.is_loop() {
+            return;
+        }
+
         // Find the index to insert a path to the new end node.
         let index = self
             .paths
@@ -222,7 +267,7 @@ impl Route {
             .map(|i| {
                 let (curr_port, _) = self.paths[i];
                 let (next_port, _) = self.paths[i + 1];
-                let (_, route) = find_path(map, ports, &waypoints, curr_port, next_port)
+                let (_, route) = find_path(grid, occupied, curr_port, next_port)
                     .expect("Did not find valid route");
                 route.into_iter().map(Waypoint::from).collect::<Vec<_>>()
             }).collect::<Vec<_>>();
@@ -233,6 +278,226 @@ impl Route {
         }
     }
 
+    /// Inserts the given port into the link containing the given waypoint,
+    /// re-pathing the link into one path to the port and one from it.
+    /// Ships on the link are kept on whichever new path they are on, or
+    /// moved to the end of the link they were heading away from.
+    /// Returns true if the port was inserted.
+    pub fn insert_port(
+        &mut self,
+        grid: &NavigationGrid,
+        occupied: &HashSet<Waypoint>,
+        waypoint: Waypoint,
+        port: Position,
+    ) -> bool {
+        let num_links = self.num_links();
+        let index = match self.paths[..num_links]
+            .iter()
+            .position(|(_, path)| path.contains(&waypoint))
+        {
+            Some(index) if !self.ports().any(|p| *p == port) => index,
+            _ => return false,
+        };
+        let (start, ref old_path) = self.paths[index];
+        let old_path = old_path.clone();
+        let (end, _) = self.paths[index + 1];
+
+        // The link being replaced should not count as overlapping.
+        let occupied = occupied
+            .iter()
+            .filter(|w| !old_path.contains(w))
+            .cloned()
+            .collect::<HashSet<_>>();
+        let (to_port, from_port) = match (
+            find_path(grid, &occupied, start, port),
+            find_path(grid, &occupied, port, end),
+        ) {
+            (Some((_, to_port)), Some((_, from_port))) => (
+                to_port.into_iter().map(Waypoint::from).collect::<Vec<_>>(),
+                from_port
+                    .into_iter()
+                    .map(Waypoint::from)
+                    .collect::<Vec<_>>(),
+            ),
+            _ => return false,
+        };
+
+        for ship in &mut self.ships {
+            if ship.path() != old_path.as_slice() {
+                continue;
+            }
+            let (current, reverse) = (ship.position(), ship.reverse());
+            // Whether the ship can keep moving from its waypoint on the path.
+            let is_on = |path: &[Waypoint]| match path.iter().position(|w| *w == current) {
+                Some(i) if reverse => i > 0,
+                Some(i) => i + 1 < path.len(),
+                None => false,
+            };
+            if is_on(&to_port) {
+                ship.reassign(current, to_port.clone(), reverse);
+            } else if is_on(&from_port) {
+                ship.reassign(current, from_port.clone(), reverse);
+            } else if reverse {
+                ship.reassign(Waypoint::from(end), from_port.clone(), true);
+            } else {
+                ship.reassign(Waypoint::from(start), to_port.clone(), false);
+            }
+        }
+
+        self.paths[index].1 = to_port;
+        self.paths.insert(index + 1, (port, from_port));
+        true
+    }
+
+    /// Returns the number of links between ports on this route.
+    pub fn num_links(&self) -> usize {
+        self.paths.len().saturating_sub(1)
+    }
+
+    /// Removes the link containing the given waypoint, a port giving the
+    /// link leading to it. Links at either end shorten the route, while
+    /// removing a link in the middle splits the route in two, keeping the
+    /// side with the most links and returning the other, if splitting is
+    /// allowed. Removing a link of a loop opens it up at that link instead.
+    /// Ships on the removed link are moved to the nearest remaining link.
+    /// The last link cannot be removed, as the route would be empty.
+    pub fn remove_link(&mut self, waypoint: Waypoint, can_split: bool) -> LinkRemoval {
+        let num_links = self.num_links();
+        let index = match self.paths[..num_links]
+            .iter()
+            .position(|(_, path)| path.contains(&waypoint))
+        {
+            Some(index) if num_links > 1 => index,
+            _ => return LinkRemoval::Kept,
+        };
+
+        if self.is_loop() {
+            self.open_loop(index);
+            return LinkRemoval::Removed;
+        }
+        if index > 0 && index < num_links - 1 {
+            if !can_split {
+                return LinkRemoval::Refused;
+            }
+            return LinkRemoval::Split(self.split(index));
+        }
+
+        // Move ships on the removed link to the nearest end of the others.
+        let removed = self.paths[index].1.clone();
+        let (port, path, reverse) = if index == 0 {
+            (self.paths[1].0, self.paths[1].1.clone(), false)
+        } else {
+            (self.paths[index].0, self.paths[index - 1].1.clone(), true)
+        };
+        for ship in &mut self.ships {
+            if ship.path() == removed.as_slice() {
+                ship.reassign(Waypoint::from(port), path.clone(), reverse);
+            }
+        }
+
+        if index == 0 {
+            self.paths.remove(0);
+        } else {
+            self.paths.pop();
+            self.paths[index].1.clear();
+        }
+        LinkRemoval::Removed
+    }
+
+    /// Splits the route by removing the link at the given index, which is
+    /// neither the first nor the last. The side with the most links is kept
+    /// and the other one returned, along with the ships on it. Ships on the
+    /// removed link go to the side they were heading to.
+    fn split(&mut self, index: usize) -> Route {
+        let removed = self.paths[index].1.clone();
+        let (start, _) = self.paths[index];
+        let (end, ref next) = self.paths[index + 1];
+        let next = next.clone();
+        let previous = self.paths[index - 1].1.clone();
+
+        let mut after = Route {
+            ships: vec![],
+            paths: self.paths.split_off(index + 1),
+        };
+        self.paths[index].1.clear();
+
+        for mut ship in self.ships.drain(..).collect::<Vec<_>>() {
+            if ship.path() == removed.as_slice() {
+                if ship.reverse() {
+                    ship.reassign(Waypoint::from(start), previous.clone(), true);
+                    self.ships.push(ship);
+                } else {
+                    ship.reassign(Waypoint::from(end), next.clone(), false);
+                    after.ships.push(ship);
+                }
+            } else if after
+                .paths
+                .iter()
+                .any(|(_, path)| path.as_slice() == ship.path())
+            {
+                after.ships.push(ship);
+            } else {
+                self.ships.push(ship);
+            }
+        }
+
+        if after.num_links() > self.num_links() {
+            mem::swap(self, &mut after);
+        }
+        after
+    }
+
+    /// Opens a loop by removing the link at the given index, so the route
+    /// starts at the port after it and ends at the port before it.
+    fn open_loop(&mut self, index: usize) {
+        let num_links = self.num_links();
+        let (end, _) = self.paths[index];
+        let previous = self.paths[(index + num_links - 1) % num_links].1.clone();
+        let removed = self.paths[index].1.clone();
+
+        // Move ships on the removed link to the new end of the route.
+        for ship in &mut self.ships {
+            if ship.path() == removed.as_slice() {
+                ship.reassign(Waypoint::from(end), previous.clone(), true);
+            }
+        }
+
+        let mut paths = self.paths[index + 1..num_links].to_vec();
+        paths.extend_from_slice(&self.paths[..index]);
+        paths.push((end, vec![]));
+        self.paths = paths;
+    }
+
+    /// Returns the layout of this route.
+    pub fn layout(&self) -> RouteLayout {
+        RouteLayout {
+            paths: self.paths.clone(),
+            ships: self.ships.clone(),
+        }
+    }
+
+    /// Restores this route to the given layout, taking its ships out of the
+    /// given ships, told apart by their identity. Ships taken keep their
+    /// cargo, and also their place if still on the same link heading the
+    /// same way. Ships of the layout not given are restored as they were.
+    pub fn restore(&mut self, layout: RouteLayout, ships: &mut Vec<Ship>) {
+        self.ships.clear();
+        for saved in layout.ships {
+            let ship = match ships.iter().position(|ship| ship.id() == saved.id()) {
+                Some(index) => {
+                    let mut ship = ships.swap_remove(index);
+                    if ship.path() != saved.path() || ship.reverse() != saved.reverse() {
+                        ship.reassign(saved.position(), saved.path().to_vec(), saved.reverse());
+                    }
+                    ship
+                }
+                None => saved,
+            };
+            self.ships.push(ship);
+        }
+        self.paths = layout.paths;
+    }
+
     /// Returns a vector with all waypoints on this route.
     pub fn waypoints(&self) -> Vec<&Waypoint> {
         self.paths
@@ -249,3 +514,224 @@ impl Route {
             .any(|w| *w == waypoint)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::DifficultySettings;
+    use map::{generator::seeded_rng, MapData};
+    use ship::ShipClass;
+
+    /// Creates a world of a strait with the given number of open ports.
+    fn strait(ports: usize) -> World {
+        let ports = (0..ports).map(|_| ("Blue", "Red", 0)).collect::<Vec<_>>();
+        MapData::strait(&ports)
+            .into_world(&mut seeded_rng(0))
+            .unwrap()
+    }
+
+    /// Returns the position of the port of the given index.
+    fn port(world: &World, index: usize) -> Position {
+        world.ports()[index].position()
+    }
+
+    /// Links the ports of the given indices in order with the given line.
+    fn link(world: &mut World, shape: RouteShape, ports: &[usize]) {
+        for pair in ports.windows(2) {
+            let (start, end) = (port(world, pair[0]), port(world, pair[1]));
+            let (_, path) = world.route(start, end).unwrap();
+            world.add_route(
+                shape,
+                start,
+                end,
+                path.into_iter().map(Waypoint::from).collect(),
+            );
+        }
+    }
+
+    /// Returns the route of the given line.
+    fn route(world: &World, shape: RouteShape) -> &Route {
+        world
+            .routes()
+            .find(|(s, _)| **s == shape)
+            .map(|(_, route)| route)
+            .unwrap()
+    }
+
+    /// Returns the indices of the ports on the route of the given line.
+    fn port_indices(world: &World, shape: RouteShape) -> Vec<usize> {
+        route(world, shape)
+            .ports()
+            .map(|p| {
+                world
+                    .ports()
+                    .iter()
+                    .position(|q| q.position() == *p)
+                    .unwrap()
+            }).collect()
+    }
+
+    /// Returns the waypoint halfway along the link of the given index.
+    fn midway(world: &World, shape: RouteShape, link: usize) -> Waypoint {
+        let (_, path) = &route(world, shape).paths[link];
+        path[path.len() / 2]
+    }
+
+    /// Adds a ship halfway along the link of the given index, returning
+    /// where it was placed.
+    fn add_ship(world: &mut World, shape: RouteShape, link: usize, reverse: bool) -> Waypoint {
+        let waypoint = midway(world, shape, link);
+        let (_, route) = world.routes_mut().find(|(s, _)| **s == shape).unwrap();
+        let path = route.paths[link].1.clone();
+        let mut ship = Ship::new(
+            ShipClass::Sloop,
+            &DifficultySettings::default(),
+            waypoint,
+            path.clone(),
+        );
+        ship.reassign(waypoint, path, reverse);
+        route.add_ship(ship);
+        waypoint
+    }
+
+    #[test]
+    fn removes_links_at_either_end() {
+        let mut world = strait(4);
+        let shape = world.lines()[0];
+        link(&mut world, shape, &[0, 1, 2, 3]);
+        add_ship(&mut world, shape, 0, false);
+
+        let waypoint = midway(&world, shape, 0);
+        assert!(world.remove_links_at(waypoint).is_empty());
+        assert_eq!(port_indices(&world, shape), vec![1, 2, 3]);
+        let ship = route(&world, shape).ships().next().unwrap();
+        assert_eq!(ship.position(), Waypoint::from(port(&world, 1)));
+        assert_eq!(ship.path(), route(&world, shape).paths[0].1.as_slice());
+
+        let waypoint = midway(&world, shape, 1);
+        assert!(world.remove_links_at(waypoint).is_empty());
+        assert_eq!(port_indices(&world, shape), vec![1, 2]);
+        assert!(route(&world, shape).paths[1].1.is_empty());
+
+        // Removing the last link removes the route and returns its ships.
+        let waypoint = midway(&world, shape, 0);
+        assert_eq!(world.remove_links_at(waypoint).len(), 1);
+        assert_eq!(world.routes().count(), 0);
+    }
+
+    #[test]
+    fn splits_route_at_middle_link() {
+        let mut world = strait(5);
+        let (shape, free) = (world.lines()[0], world.lines()[1]);
+        link(&mut world, shape, &[0, 1, 2, 3, 4]);
+        let before = add_ship(&mut world, shape, 0, false);
+        let after = add_ship(&mut world, shape, 3, true);
+        add_ship(&mut world, shape, 1, false);
+
+        let waypoint = midway(&world, shape, 1);
+        assert!(world.remove_links_at(waypoint).is_empty());
+
+        // The longer side keeps the line, the other takes a free one.
+        assert_eq!(port_indices(&world, shape), vec![2, 3, 4]);
+        assert_eq!(port_indices(&world, free), vec![0, 1]);
+
+        // Only the ship on the removed link is moved, to where it headed.
+        let ships = route(&world, shape).ships().collect::<Vec<_>>();
+        assert_eq!(ships.len(), 2);
+        assert_eq!(ships[0].position(), after);
+        assert_eq!(ships[1].position(), Waypoint::from(port(&world, 2)));
+        assert_eq!(ships[1].path(), route(&world, shape).paths[0].1.as_slice());
+        let ships = route(&world, free).ships().collect::<Vec<_>>();
+        assert_eq!(ships.len(), 1);
+        assert_eq!(ships[0].position(), before);
+    }
+
+    #[test]
+    fn keeps_route_whole_without_free_line() {
+        let mut world = strait(5);
+        let shapes = world.lines().to_vec();
+        link(&mut world, shapes[0], &[0, 1, 2, 3, 4]);
+        link(&mut world, shapes[1], &[3, 4]);
+        link(&mut world, shapes[2], &[3, 4]);
+        add_ship(&mut world, shapes[0], 0, false);
+
+        let waypoint = midway(&world, shapes[0], 1);
+        assert!(world.remove_links_at(waypoint).is_empty());
+        assert_eq!(port_indices(&world, shapes[0]), vec![0, 1, 2, 3, 4]);
+        assert_eq!(route(&world, shapes[0]).ships().count(), 1);
+        assert_eq!(world.routes().count(), 3);
+
+        // Links at the ends are still removed.
+        let waypoint = midway(&world, shapes[0], 0);
+        assert!(world.remove_links_at(waypoint).is_empty());
+        assert_eq!(port_indices(&world, shapes[0]), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn closes_route_into_loop() {
+        let mut world = strait(3);
+        let shape = world.lines()[0];
+        link(&mut world, shape, &[0, 1, 2]);
+        assert!(!route(&world, shape).is_loop());
+        assert!(world
+            .allowed_ends(port(&world, 2), shape)
+            .contains(&port(&world, 0)));
+
+        link(&mut world, shape, &[2, 0]);
+        let route = route(&world, shape);
+        assert!(route.is_loop());
+        assert_eq!(port_indices(&world, shape), vec![0, 1, 2, 0]);
+        assert!(world.allowed_starts(shape).is_empty());
+        assert!(world.allowed_ends(port(&world, 2), shape).is_empty());
+
+        // Ships circulate, leaving the last port along the first link.
+        assert_eq!(
+            route.next_path(port(&world, 0)),
+            Some(route.paths[0].1.clone())
+        );
+        assert_eq!(route.previous_path(port(&world, 1)), None);
+    }
+
+    #[test]
+    fn opens_loop_at_removed_link() {
+        let mut world = strait(3);
+        let shape = world.lines()[0];
+        link(&mut world, shape, &[0, 1, 2, 0]);
+        add_ship(&mut world, shape, 1, false);
+
+        let waypoint = midway(&world, shape, 1);
+        assert!(world.remove_links_at(waypoint).is_empty());
+        assert!(!route(&world, shape).is_loop());
+        assert_eq!(port_indices(&world, shape), vec![2, 0, 1]);
+
+        // The ship turns back at the new end of the route.
+        let route = route(&world, shape);
+        let ship = route.ships().next().unwrap();
+        assert_eq!(ship.position(), Waypoint::from(port(&world, 1)));
+        assert_eq!(ship.path(), route.paths[1].1.as_slice());
+        assert!(ship.reverse());
+    }
+
+    #[test]
+    fn inserts_port_into_link() {
+        let mut world = strait(3);
+        let shape = world.lines()[0];
+        link(&mut world, shape, &[0, 2]);
+        let position = add_ship(&mut world, shape, 0, false);
+        assert_eq!(world.allowed_inserts(shape), vec![port(&world, 1)]);
+
+        // Ports already on the route can not be inserted.
+        let waypoint = midway(&world, shape, 0);
+        assert!(!world.insert_port(shape, waypoint, port(&world, 2)));
+        assert!(world.insert_port(shape, waypoint, port(&world, 1)));
+        assert_eq!(port_indices(&world, shape), vec![0, 1, 2]);
+        assert!(world.allowed_inserts(shape).is_empty());
+
+        // The ship stays where it was, on the new path leading there.
+        let route = route(&world, shape);
+        let ship = route.ships().next().unwrap();
+        assert_eq!(ship.position(), position);
+        assert_eq!(ship.path(), route.paths[0].1.as_slice());
+        assert_eq!(ship.path().last(), Some(&Waypoint::from(port(&world, 1))));
+    }
+}
@@ -0,0 +1,71 @@
+use super::*;
+
+/// Grid of the tiles a trade route can pass through, i.e. water and open
+/// ports, stored in row-major order for constant time lookup by position.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct NavigationGrid {
+    width: i32,
+    height: i32,
+    passable: Vec<bool>,
+}
+
+impl NavigationGrid {
+    /// Creates a new navigation grid from the given tiles and open ports.
+    /// The grid size is given by the tile furthest from origo.
+    pub fn new(map: &[Tile], ports: &[Port]) -> Self {
+        let width = map.iter().map(|t| t.position().x + 1).max().unwrap_or(0);
+        let height = map.iter().map(|t| t.position().y + 1).max().unwrap_or(0);
+        let mut grid = NavigationGrid {
+            width,
+            height,
+            passable: vec![false; (width * height) as usize],
+        };
+        for tile in map {
+            grid.set_passable(tile.position(), tile.kind() == TileKind::Water);
+        }
+        for port in ports {
+            grid.set_passable(port.position(), true);
+        }
+        grid
+    }
+
+    /// Returns the index of the given position, if inside the grid.
+    fn index(&self, position: Position) -> Option<usize> {
+        if position.x < 0 || position.y < 0 || position.x >= self.width || position.y >= self.height
+        {
+            None
+        } else {
+            Some((position.y * self.width + position.x) as usize)
+        }
+    }
+
+    /// Returns whether a trade route can pass through the given position.
+    pub fn is_passable(&self, position: Position) -> bool {
+        self.index(position)
+            .map_or(false, |index| self.passable[index])
+    }
+
+    /// Sets whether a trade route can pass through the given position,
+    /// doing nothing if it is outside the grid.
+    pub fn set_passable(&mut self, position: Position, passable: bool) {
+        if let Some(index) = self.index(position) {
+            self.passable[index] = passable;
+        }
+    }
+
+    /// Returns all neighbors (north, east, south, west) of the given position
+    /// which a trade route can pass through.
+    pub fn reachable(&self, position: Position) -> Vec<Position> {
+        let (x, y) = (position.x, position.y);
+        [
+            Position::new(x, y - 1),
+            Position::new(x + 1, y),
+            Position::new(x, y + 1),
+            Position::new(x - 1, y),
+        ]
+        .iter()
+        .cloned()
+        .filter(|p| self.is_passable(*p))
+        .collect()
+    }
+}
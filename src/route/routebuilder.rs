@@ -5,6 +5,7 @@ pub struct RouteBuilder {
     from: Position,
     to: Position,
     path: Option<Vec<Waypoint>>,
+    segment: Option<Waypoint>,
 }
 
 impl RouteBuilder {
@@ -14,6 +15,16 @@ impl RouteBuilder {
             from,
             to: from,
             path: None,
+            segment: None,
+        }
+    }
+
+    /// Create a new route builder dragging the segment of an existing route
+    /// through the given waypoint, to insert a port into it.
+    pub fn on_segment(waypoint: Position) -> Self {
+        RouteBuilder {
+            segment: Some(Waypoint::from(waypoint)),
+            ..RouteBuilder::new(waypoint)
         }
     }
 
@@ -33,6 +44,11 @@ impl RouteBuilder {
         &self.path
     }
 
+    /// Returns the waypoint of the segment being dragged, if any.
+    pub fn segment(&self) -> Option<Waypoint> {
+        self.segment
+    }
+
     /// Returns the starting position.
     pub fn from(&self) -> &Position {
         &self.from
@@ -1,18 +1,74 @@
 use super::*;
 use animation::Animation;
+use resource;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-/// Represents the visual display of the route path.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum RouteShape {
-    Plus,
-    Cross,
-    Star,
+/// Number of lines available from the start of sessions without their own
+/// progression.
+pub const DEFAULT_LINES: usize = 3;
+
+/// A route line, given by its name, the column of its shape in the tileset
+/// and the color it is tinted with.
+#[derive(Deserialize, Debug)]
+struct Line {
+    name: String,
+    sprite: u32,
+    rgb: (u8, u8, u8),
+}
+
+lazy_static! {
+    /// All route lines which can be drawn, in the order they are unlocked.
+    /// Their count bounds how many routes a session can hold.
+    static ref LINES: Vec<Line> = resource::load_list("lines.json");
 }
 
+/// Represents the visual display of the route path, one for each route
+/// line. Saves refer to a line by its name, which stays valid when lines
+/// are reordered or added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RouteShape(usize);
+
 impl RouteShape {
-    /// Returns all shape variants.
+    /// Returns the shape of the line with the given name, if any.
+    pub fn from_name(name: &str) -> Option<RouteShape> {
+        LINES
+            .iter()
+            .position(|line| line.name == name)
+            .map(RouteShape)
+    }
+
+    /// Returns the name of the line.
+    pub fn name(self) -> &'static str {
+        &LINES[self.0].name
+    }
+
+    /// Returns the column of the shape in the tileset.
+    pub fn sprite(self) -> f32 {
+        LINES[self.0].sprite as f32
+    }
+
+    /// Returns a tuple of the tint's components in the RGB scheme.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        LINES[self.0].rgb
+    }
+
+    /// Returns all shapes, in the order they are unlocked.
     pub fn values() -> Vec<RouteShape> {
-        vec![RouteShape::Plus, RouteShape::Cross, RouteShape::Star]
+        (0..LINES.len()).map(RouteShape).collect()
+    }
+}
+
+impl Serialize for RouteShape {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for RouteShape {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RouteShape, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        RouteShape::from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("unknown route line: {}", name)))
     }
 }
 
@@ -24,15 +80,23 @@ pub struct ShapeSelector {
 }
 
 impl ShapeSelector {
-    /// Create a new shape seclector.
-    pub fn new() -> Self {
+    /// Create a new shape seclector of the given shapes.
+    pub fn new(shapes: Vec<RouteShape>) -> Self {
         ShapeSelector {
             selected: None,
-            shapes: RouteShape::values(),
+            shapes,
             animation: None,
         }
     }
 
+    /// Replaces the shapes available in this selector, keeping the
+    /// selection if the selected shape is still available.
+    pub fn set_shapes(&mut self, shapes: Vec<RouteShape>) {
+        let selected = self.selected();
+        self.shapes = shapes;
+        self.selected = selected.and_then(|shape| self.find(shape));
+    }
+
     /// Returns the selected shape, if any.
     pub fn selected(&self) -> Option<RouteShape> {
         if let Some(index) = self.selected {
@@ -62,6 +126,13 @@ impl ShapeSelector {
         self.shapes.iter()
     }
 
+    /// Returns the center of the shape of the given index in grid cells,
+    /// the shapes being spread out centered below the grid.
+    pub fn shape_position(&self, index: usize, config: &Config) -> Point2 {
+        let x_offset = config.grid_width as f32 / 2. - self.shapes.len() as f32 + 1.;
+        Point2::new(x_offset + 2. * index as f32, config.grid_height as f32 + 1.)
+    }
+
     pub fn animation_mut(&mut self) -> &mut Option<Animation> {
         &mut self.animation
     }
@@ -75,9 +146,6 @@ impl<'a> Drawable<'a> for ShapeSelector {
     }
 
     fn draw(&self, data: &Config) -> Vec<DrawParam> {
-        let num_shapes = self.shapes().count() as u32;
-        let shape_selector_x_offset = (data.grid_width / 2 - num_shapes + 1) as f32;
-        let shape_selector_y_offset = (data.grid_height as f32 + 1.) as f32;
         self.shapes
             .iter()
             .enumerate()
@@ -88,28 +156,22 @@ impl<'a> Drawable<'a> for ShapeSelector {
                     _ => 1.8,
                 };
 
-                let x_offset = match shape {
-                    RouteShape::Plus => 0.,
-                    RouteShape::Cross => 1.,
-                    RouteShape::Star => 2.,
-                } * Self::TILE_OFFSET;
+                let (r, g, b) = shape.rgb();
 
                 DrawParam {
                     src: Rect::new(
-                        x_offset,
+                        shape.sprite() * Self::TILE_OFFSET,
                         3. * Self::TILE_SIZE,
                         Self::TILE_SIZE,
                         Self::TILE_SIZE,
                     ),
-                    dest: Point2::new(
-                        shape_selector_x_offset + 2. * index as f32 as f32,
-                        shape_selector_y_offset,
-                    ),
+                    dest: self.shape_position(index, data),
                     scale: Point2::new(scale_factor, scale_factor),
                     offset: Point2::new(0.5, 0.5),
-                    color: Some(ggezColor::from_rgb(0, 0, 0)), // TODO: Change placeholder color.
+                    color: Some(ggezColor::from_rgb(r, g, b)),
                     ..Default::default()
                 }
-            }).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
     }
 }
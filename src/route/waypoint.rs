@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 /// A waypoint is a drawable position.
 pub struct Waypoint(Position);
 
@@ -8,20 +8,16 @@ impl<'a> Drawable<'a> for Waypoint {
     type Data = RouteShape;
 
     fn draw(&self, shape: &RouteShape) -> Vec<DrawParam> {
-        let x_offset = match shape {
-            RouteShape::Plus => 0.,
-            RouteShape::Cross => 1.,
-            RouteShape::Star => 2.,
-        } * Self::TILE_OFFSET;
+        let (r, g, b) = shape.rgb();
         vec![DrawParam {
             src: Rect::new(
-                x_offset,
+                shape.sprite() * Self::TILE_OFFSET,
                 3. * Self::TILE_OFFSET,
                 Self::TILE_SIZE,
                 Self::TILE_SIZE,
             ),
             dest: Point2::from(self.0),
-            color: Some(ggezColor::from_rgb(0, 0, 0)), // TODO: Change placeholder color.
+            color: Some(ggezColor::from_rgb(r, g, b)),
             ..Default::default()
         }]
     }
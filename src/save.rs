@@ -0,0 +1,79 @@
+use ggez::{Context, GameError};
+use serde_json;
+use std::{error::Error, fmt, io};
+
+use simulation::{SavedSimulation, Simulation};
+
+/// Path in the user directory of the saved game.
+pub const SAVE_PATH: &str = "/save.json";
+
+/// Errors which can occur while saving or loading a game or high scores.
+#[derive(Debug)]
+pub enum SaveError {
+    /// The file could not be read or written.
+    Io(io::Error),
+    /// The file is not valid JSON or does not match the expected format.
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "failed to access save file: {}", err),
+            SaveError::Json(err) => write!(f, "failed to parse save file: {}", err),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Json(err)
+    }
+}
+
+impl From<GameError> for SaveError {
+    fn from(err: GameError) -> Self {
+        SaveError::Io(io::Error::new(io::ErrorKind::Other, err.to_string()))
+    }
+}
+
+impl From<SaveError> for GameError {
+    fn from(err: SaveError) -> Self {
+        GameError::ResourceLoadError(err.to_string())
+    }
+}
+
+/// Returns whether there is a saved game to resume.
+pub fn exists(ctx: &Context) -> bool {
+    ctx.filesystem.is_file(SAVE_PATH)
+}
+
+/// Saves the simulation to the user directory, replacing any earlier save.
+pub fn save(ctx: &mut Context, simulation: &mut Simulation) -> Result<(), SaveError> {
+    let file = ctx.filesystem.create(SAVE_PATH)?;
+    serde_json::to_writer(file, &simulation.save())?;
+    Ok(())
+}
+
+/// Loads the saved simulation from the user directory.
+pub fn load(ctx: &mut Context) -> Result<Simulation, SaveError> {
+    let file = ctx.filesystem.open(SAVE_PATH)?;
+    let saved: SavedSimulation = serde_json::from_reader(file)?;
+    Ok(Simulation::restore(saved))
+}
+
+/// Removes the saved game, if any.
+pub fn remove(ctx: &mut Context) -> Result<(), SaveError> {
+    if exists(ctx) {
+        ctx.filesystem.delete(SAVE_PATH)?;
+    }
+    Ok(())
+}
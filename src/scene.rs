@@ -0,0 +1,217 @@
+use ggez::{
+    event::{self, Keycode, Mod, MouseButton, MouseState},
+    graphics, timer, Context, GameResult,
+};
+
+/// Change of scenes requested by the top scene when updated.
+pub enum Transition {
+    /// Keep the current scene.
+    None,
+    /// Put a new scene on top of the current one.
+    Push(Box<dyn Scene>),
+    /// Remove the current scene, returning to the one below.
+    Pop,
+    /// Replace the current scene with a new one.
+    Replace(Box<dyn Scene>),
+    /// Leave the game.
+    Quit,
+}
+
+/// A screen of the game, such as the main menu or a game session.
+/// Only the top scene of the stack is updated and receives input.
+pub trait Scene {
+    /// Updates the scene, returning the transition to make.
+    fn update(&mut self, ctx: &mut Context) -> GameResult<Transition>;
+
+    /// Draws the scene. Clearing and presenting is done by the scene stack.
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()>;
+
+    /// Returns whether the scene is drawn on top of the scene below.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Called when the scene is the top scene again after the scene above
+    /// it was popped.
+    fn resumed(&mut self, _ctx: &mut Context) {}
+
+    /// Handle mouse down events.
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: MouseButton,
+        _x: i32,
+        _y: i32,
+    ) {
+    }
+
+    /// Handle mouse up events.
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: MouseButton,
+        _x: i32,
+        _y: i32,
+    ) {
+    }
+
+    /// Handle mouse movement events.
+    fn mouse_motion_event(
+        &mut self,
+        _ctx: &mut Context,
+        _state: MouseState,
+        _x: i32,
+        _y: i32,
+        _xrel: i32,
+        _yrel: i32,
+    ) {
+    }
+
+    /// Handle key presses.
+    fn key_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        _keycode: Keycode,
+        _keymod: Mod,
+        _repeat: bool,
+    ) {
+    }
+
+    /// Handle the window gaining or losing focus.
+    fn focus_event(&mut self, _ctx: &mut Context, _gained: bool) {}
+
+    /// Called on all scenes when the game is about to close.
+    fn quit_event(&mut self, _ctx: &mut Context) {}
+}
+
+/// Stack of scenes run by a single event loop, forwarding events to the
+/// top scene and applying its transitions.
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    frames: usize,
+}
+
+impl SceneStack {
+    /// Creates a new scene stack with the given initial scene.
+    pub fn new(scene: Box<dyn Scene>) -> Self {
+        SceneStack {
+            scenes: vec![scene],
+            frames: 0,
+        }
+    }
+
+    /// Applies the given transition, quitting if no scene is left.
+    fn apply(&mut self, ctx: &mut Context, transition: Transition) {
+        match transition {
+            Transition::None => return,
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => {
+                self.scenes.pop();
+                if let Some(scene) = self.scenes.last_mut() {
+                    scene.resumed(ctx);
+                }
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+            Transition::Quit => self.scenes.clear(),
+        }
+        if self.scenes.is_empty() {
+            ctx.quit().expect("Failed to quit game");
+        }
+    }
+}
+
+impl event::EventHandler for SceneStack {
+    /// Updates the top scene and applies its transition.
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let transition = match self.scenes.last_mut() {
+            Some(scene) => scene.update(ctx)?,
+            None => Transition::None,
+        };
+        self.apply(ctx, transition);
+        Ok(())
+    }
+
+    /// Forwards mouse down events to the top scene.
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: i32, y: i32) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_button_down_event(ctx, button, x, y);
+        }
+    }
+
+    /// Forwards mouse up events to the top scene.
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: i32, y: i32) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_button_up_event(ctx, button, x, y);
+        }
+    }
+
+    /// Forwards mouse movement events to the top scene.
+    fn mouse_motion_event(
+        &mut self,
+        ctx: &mut Context,
+        state: MouseState,
+        x: i32,
+        y: i32,
+        xrel: i32,
+        yrel: i32,
+    ) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_motion_event(ctx, state, x, y, xrel, yrel);
+        }
+    }
+
+    /// Forwards key presses to the top scene.
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, keymod: Mod, repeat: bool) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.key_down_event(ctx, keycode, keymod, repeat);
+        }
+    }
+
+    /// Forwards focus changes to the top scene.
+    fn focus_event(&mut self, ctx: &mut Context, gained: bool) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.focus_event(ctx, gained);
+        }
+    }
+
+    /// Lets all scenes react to the game closing.
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        for scene in self.scenes.iter_mut().rev() {
+            scene.quit_event(ctx);
+        }
+        false
+    }
+
+    /// Draws the top scene, along with the scenes below it if overlaid.
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if self.frames % 100 == 0 {
+            println!("FPS: {:.1}", timer::get_fps(ctx));
+        }
+        graphics::clear(ctx);
+
+        // Draw from the topmost scene which is not an overlay.
+        let first_visible = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        for scene in self.scenes.iter_mut().skip(first_visible) {
+            scene.draw(ctx)?;
+        }
+
+        graphics::present(ctx);
+        self.frames += 1;
+        // And yield the timeslice
+        // This tells the OS that we're done using the CPU but it should
+        // get back to this program as soon as it can.
+        // This ideally prevents the game from using 100% CPU all the time
+        // even if vsync is off.
+        // The actual behavior can be a little platform-specific.
+        timer::yield_now();
+
+        Ok(())
+    }
+}
@@ -1,43 +1,83 @@
 use ggez::{
-    event, graphics, timer, {Context, GameResult},
+    event::{self, Keycode, Mod},
+    graphics, {Context, GameResult},
 };
 
-use color::Color;
 use fonts::FontCache;
-use std::cell::RefCell;
-use tally::Tally;
+use highscore::{HighScoreEntry, HighScores};
+use scene::{Scene, Transition};
 
 /// Handles and displays the score board.
-pub struct ScoreState<'a> {
+pub struct ScoreState {
     font_cache: FontCache,
-    frames: usize,
-    tally: &'a RefCell<Tally>,
+    result: HighScoreEntry,
+    high_scores: HighScores,
+    rank: Option<usize>,
+    is_leaving: bool,
 }
 
-impl<'a> ScoreState<'a> {
-    /// Creates a new score board state.
-    pub fn new(ctx: &mut Context, tally: &'a RefCell<Tally>) -> GameResult<Self> {
+impl ScoreState {
+    /// Creates a new score board state, adding the result to the
+    /// high-score table.
+    pub fn new(ctx: &mut Context, result: HighScoreEntry) -> GameResult<Self> {
+        let mut high_scores = HighScores::load(ctx).unwrap_or_else(|err| {
+            println!("Failed to load high scores, starting new table: {}", err);
+            HighScores::default()
+        });
+        let rank = high_scores.insert(result.clone());
+        if rank.is_some() {
+            if let Err(err) = high_scores.save(ctx) {
+                println!("Failed to save high scores: {}", err);
+            }
+        }
+
         let state = ScoreState {
             font_cache: FontCache::new(ctx),
-            frames: 0,
-            tally,
+            result,
+            high_scores,
+            rank,
+            is_leaving: false,
         };
         Ok(state)
     }
 }
 
-impl<'a> event::EventHandler for ScoreState<'a> {
-    /// Updates the score board state.
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        Ok(())
+impl Scene for ScoreState {
+    /// Updates the score board state, returning to the menu when leaving.
+    fn update(&mut self, _ctx: &mut Context) -> GameResult<Transition> {
+        if self.is_leaving {
+            Ok(Transition::Pop)
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    /// Handle mouse down events, leaving the score board.
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: event::MouseButton,
+        _x: i32,
+        _y: i32,
+    ) {
+        self.is_leaving = true;
+    }
+
+    /// Handle key presses, leaving the score board.
+    fn key_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        _keycode: Keycode,
+        _keymod: Mod,
+        repeat: bool,
+    ) {
+        if !repeat {
+            self.is_leaving = true;
+        }
     }
 
     /// Draws the current state to the screen with the given context.
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        if self.frames % 100 == 0 {
-            println!("FPS: {:.1}", timer::get_fps(ctx));
-        }
-        graphics::clear(ctx);
         graphics::set_background_color(ctx, graphics::Color::from((243, 243, 236)));
 
         let (window_width, window_height) = graphics::get_drawable_size(ctx);
@@ -55,15 +95,20 @@ impl<'a> event::EventHandler for ScoreState<'a> {
             0.,
         )?;
 
-        let tally = self.tally.try_borrow().expect("Failed to read tally");
+        let collected = self
+            .result
+            .tally()
+            .entries()
+            .iter()
+            .map(|(color, amount)| format!("{}: {}", color.name(), amount))
+            .collect::<Vec<_>>();
         let start_game = graphics::Text::new(
             ctx,
             &format!(
-                "Score: {}\nRed: {}, Green: {}, Blue:{}",
-                tally.score(),
-                tally.get(Color::Red),
-                tally.get(Color::Green),
-                tally.get(Color::Blue)
+                "{}\n{}\nSeed: {}",
+                self.result.result_text(),
+                collected.join(", "),
+                self.result.seed()
             ),
             self.font_cache.medium(),
         )?;
@@ -78,19 +123,19 @@ impl<'a> event::EventHandler for ScoreState<'a> {
             0.,
         )?;
 
+        // Draw high-score table, highlighting the new result.
+        let high_scores_y_offset = start_game_y_offset + start_game.height() as f32 * 1.5;
+        self.high_scores.paint(
+            self.font_cache.small(),
+            ctx,
+            high_scores_y_offset,
+            self.result.mode(),
+            self.rank,
+        )?;
+
         // Reset color to default (white).
         graphics::set_color(ctx, graphics::Color::from_rgb(255, 255, 255))?;
 
-        graphics::present(ctx);
-        self.frames += 1;
-        // And yield the timeslice
-        // This tells the OS that we're done using the CPU but it should
-        // get back to this program as soon as it can.
-        // This ideally prevents the game from using 100% CPU all the time
-        // even if vsync is off.
-        // The actual behavior can be a little platform-specific.
-        timer::yield_now();
-
         Ok(())
     }
 }
@@ -0,0 +1,86 @@
+use color::Color;
+use route::Waypoint;
+
+/// An amount of a single color carried from the given port.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CargoEntry {
+    pub origin: Waypoint,
+    pub color: Color,
+    pub amount: u32,
+}
+
+/// The cargo carried by a ship, holding at most its capacity of units
+/// of any mix of colors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CargoHold {
+    capacity: u32,
+    entries: Vec<CargoEntry>,
+}
+
+impl CargoHold {
+    /// Creates a new empty hold of the given capacity.
+    pub fn new(capacity: u32) -> Self {
+        CargoHold {
+            capacity,
+            entries: vec![],
+        }
+    }
+
+    /// Returns the number of units which can be carried.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Returns the number of units currently carried.
+    pub fn amount(&self) -> u32 {
+        self.entries.iter().map(|entry| entry.amount).sum()
+    }
+
+    /// Returns true if nothing is carried.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns all entries, in the order they were loaded.
+    pub fn entries(&self) -> &[CargoEntry] {
+        &self.entries
+    }
+
+    /// Fills the remaining space of the hold with at most the given amount
+    /// of the color from the given port, returning the amount loaded.
+    pub fn load(&mut self, origin: Waypoint, color: Color, available: u32) -> u32 {
+        let loaded = (self.capacity - self.amount()).min(available);
+        if loaded == 0 {
+            return 0;
+        }
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.origin == origin && entry.color == color)
+        {
+            Some(entry) => entry.amount += loaded,
+            None => self.entries.push(CargoEntry {
+                origin,
+                color,
+                amount: loaded,
+            }),
+        }
+        loaded
+    }
+
+    /// Removes at most the given amount of units of the color not loaded at
+    /// the given port, returning the amount removed. Units of other colors
+    /// are kept.
+    pub fn unload(&mut self, port: Waypoint, color: Color, wanted: u32) -> u32 {
+        let mut unloaded = 0;
+        for entry in &mut self.entries {
+            if entry.color == color && entry.origin != port {
+                let amount = entry.amount.min(wanted - unloaded);
+                entry.amount -= amount;
+                unloaded += amount;
+            }
+        }
+        self.entries.retain(|entry| entry.amount > 0);
+        unloaded
+    }
+}
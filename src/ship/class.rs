@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+/// The kind of a ship, deciding how fast it sails, how much it carries
+/// and how much of the shipyard it costs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShipClass {
+    Sloop,
+    Cog,
+    Galleon,
+}
+
+impl ShipClass {
+    /// Returns the name of the class.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShipClass::Sloop => "Sloop",
+            ShipClass::Cog => "Cog",
+            ShipClass::Galleon => "Galleon",
+        }
+    }
+
+    /// Returns the speed in tiles per second.
+    pub fn speed(self) -> f32 {
+        match self {
+            ShipClass::Sloop => 5.,
+            ShipClass::Cog => 4.,
+            ShipClass::Galleon => 3.,
+        }
+    }
+
+    /// Returns the number of cargo units carried at once.
+    pub fn capacity(self) -> u32 {
+        match self {
+            ShipClass::Sloop => 1,
+            ShipClass::Cog => 2,
+            ShipClass::Galleon => 4,
+        }
+    }
+
+    /// Returns the time spent docked at each port.
+    pub fn docking_time(self) -> Duration {
+        match self {
+            ShipClass::Sloop => Duration::from_millis(999),
+            ShipClass::Cog => Duration::from_millis(1500),
+            ShipClass::Galleon => Duration::from_millis(2000),
+        }
+    }
+
+    /// Returns the number of shipyard ships needed to build the class.
+    pub fn cost(self) -> usize {
+        match self {
+            ShipClass::Sloop => 1,
+            ShipClass::Cog => 2,
+            ShipClass::Galleon => 3,
+        }
+    }
+
+    /// Returns the column and row of the ship sprite in the tileset. All
+    /// classes share the same tile until they are given sprites of their own.
+    pub fn sprite(self) -> (f32, f32) {
+        match self {
+            ShipClass::Sloop => (3., 2.),
+            ShipClass::Cog => (3., 2.),
+            ShipClass::Galleon => (3., 2.),
+        }
+    }
+
+    /// Returns the size of the ship sprite relative to a tile, which tells
+    /// the classes apart while they share the same sprite.
+    pub fn sprite_scale(self) -> f32 {
+        match self {
+            ShipClass::Sloop => 1.,
+            ShipClass::Cog => 1.2,
+            ShipClass::Galleon => 1.4,
+        }
+    }
+
+    /// Returns all class variants.
+    pub fn values() -> Vec<ShipClass> {
+        vec![ShipClass::Sloop, ShipClass::Cog, ShipClass::Galleon]
+    }
+}
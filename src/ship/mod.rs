@@ -1,57 +1,105 @@
 use ggez::{
     graphics::{Color as ggezColor, DrawParam, Point2, Rect},
     nalgebra as na,
-    timer::get_delta,
+    timer::duration_to_f64,
     Context,
 };
-use std::time::Duration;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use animation::Animation;
 use color::Color;
-use config::Config;
+use config::{Config, DifficultySettings};
 use draw::Drawable;
 use geometry::Position;
 use route::Waypoint;
 use update::Updatable;
 use world::World;
 
+mod cargo;
+mod class;
 mod shipbuilder;
 mod shipyard;
 
+pub use self::cargo::{CargoEntry, CargoHold};
+pub use self::class::ShipClass;
 pub use self::shipbuilder::ShipBuilder;
 pub use self::shipyard::Shipyard;
 
+/// Identity of the next ship built or loaded.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a new identity for a ship, unique while the game is running.
+fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 /// A ship which transports resources between ports along a route.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Ship {
+    /// Identity of the ship, shared by its copies in the edit history.
+    /// Not saved, loaded ships are given a new one.
+    #[serde(skip, default = "next_id")]
+    id: usize,
+    class: ShipClass,
+    /// Speed in tiles per second, as given by the class and difficulty.
+    speed: f32,
+    /// Time spent docked at each port, as given by the class and difficulty.
+    docking_time: Duration,
     docked: Duration,
+    #[serde(with = "::geometry::serde_point")]
     position: Point2,
     current_waypoint: Waypoint,
     /// Current path.
     path: Vec<Waypoint>,
     /// If we are on the return trip or not.
     reverse: bool,
-    /// Color currently being carried from the given port.
-    cargo: Option<(Waypoint, Color)>,
+    /// Cargo currently being carried, with the port each unit is from.
+    cargo: CargoHold,
     animation: Option<Animation>,
 }
 
 impl Ship {
-    const SPEED: f32 = 5.;
-
-    /// Creates a new ship.
-    pub fn new(position: Waypoint, path: Vec<Waypoint>) -> Self {
+    /// Creates a new ship of the given class, sailing and docking as given
+    /// by the difficulty settings.
+    pub fn new(
+        class: ShipClass,
+        settings: &DifficultySettings,
+        position: Waypoint,
+        path: Vec<Waypoint>,
+    ) -> Self {
         Ship {
+            id: next_id(),
+            class,
+            speed: settings.speed(class),
+            docking_time: settings.docking_time(class),
             docked: Duration::from_millis(0),
             current_waypoint: position,
             position: Point2::from(Position::from(position)),
             path,
             reverse: false,
-            cargo: None,
+            cargo: CargoHold::new(class.capacity()),
             animation: None,
         }
     }
 
+    /// Returns the identity of the ship.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the class of the ship.
+    pub fn class(&self) -> ShipClass {
+        self.class
+    }
+
+    /// Returns the time spent docked at each port.
+    pub fn docking_time(&self) -> Duration {
+        self.docking_time
+    }
+
     /// Returns the ship's current position.
     pub fn position(&self) -> Waypoint {
         self.current_waypoint
@@ -66,6 +114,21 @@ impl Ship {
         &mut self.animation
     }
 
+    /// Returns the current path.
+    pub fn path(&self) -> &[Waypoint] {
+        &self.path
+    }
+
+    /// Moves the ship to the given waypoint on the given path, heading
+    /// towards the start of the path if reversed. The cargo is kept.
+    pub fn reassign(&mut self, waypoint: Waypoint, path: Vec<Waypoint>, reverse: bool) {
+        self.current_waypoint = waypoint;
+        self.position = Point2::from(Position::from(waypoint));
+        self.path = path;
+        self.reverse = reverse;
+        self.docked = Duration::from_millis(0);
+    }
+
     /// Returns the next waypoint on the ship's current route.
     /// Will return the 'previous' waypoint if based on reverse state.
     pub fn next_waypoint(&self) -> Option<Waypoint> {
@@ -75,7 +138,10 @@ impl Ship {
             .position(|w| *w == self.current_waypoint)
             .expect("Current position not on path");
         if self.reverse {
-            self.path.get(current_position - 1).cloned()
+            current_position
+                .checked_sub(1)
+                .and_then(|position| self.path.get(position))
+                .cloned()
         } else {
             self.path.get(current_position + 1).cloned()
         }
@@ -83,29 +149,24 @@ impl Ship {
 
     /// Returns whether the ships is currently docked.
     pub fn is_docked(&self) -> bool {
-        self.docked.subsec_millis() > 0
+        self.docked.as_secs() > 0 || self.docked.subsec_millis() > 0
     }
 
-    /// Returns the current cargo if any.
-    pub fn cargo(&self) -> Option<Color> {
-        self.cargo.map(|(_, c)| c)
+    /// Returns the current cargo.
+    pub fn cargo(&self) -> &CargoHold {
+        &self.cargo
     }
 
-    /// Attempts to unload the current cargo if any.
-    pub fn try_unload(&mut self) -> Option<Color> {
-        let tmp = match self.cargo {
-            Some((w, c)) if w != self.current_waypoint => Some(c),
-            _ => None,
-        };
-        if tmp.is_some() {
-            self.cargo = None;
-        }
-        tmp
+    /// Attempts to unload at most the given amount of cargo of the given
+    /// color not loaded at the current port, returning the amount unloaded.
+    pub fn try_unload(&mut self, color: Color, wanted: u32) -> u32 {
+        self.cargo.unload(self.current_waypoint, color, wanted)
     }
 
-    /// Attempts to load the given color as cargo, doing nothing if already loaded.
-    pub fn try_load(&mut self, color: Color) {
-        self.cargo = self.cargo.or_else(|| Some((self.current_waypoint, color)));
+    /// Attempts to fill the hold with at most the given amount of the given
+    /// color as cargo, returning the amount loaded.
+    pub fn try_load(&mut self, color: Color, available: u32) -> u32 {
+        self.cargo.load(self.current_waypoint, color, available)
     }
 
     /// Returns true if the next waypoint is the final waypoint on the path.
@@ -116,7 +177,10 @@ impl Ship {
             .position(|w| *w == self.current_waypoint)
             .expect("Current position not on path");
         if self.reverse {
-            self.path.get(current_position - 2).is_none()
+            current_position
+                .checked_sub(2)
+                .and_then(|position| self.path.get(position))
+                .is_none()
         } else {
             self.path.get(current_position + 2).is_none()
         }
@@ -144,17 +208,14 @@ impl<'a> Updatable<'a> for Ship {
     /// waypoint if enough time has past.
     /// Note: The next path needs to be set if it is on the final waypoint,
     /// otherwise it can be omitted.
-    fn update(&mut self, ctx: &Context, next_path: Option<Vec<Waypoint>>) {
+    fn update(&mut self, delta: Duration, next_path: Option<Vec<Waypoint>>) {
         if !self.is_docked() {
             let current_waypoint = Point2::from(Position::from(self.current_waypoint));
             let next_waypoint = Point2::from(Position::from(self.next_waypoint().unwrap()));
             let distance_to_next = na::distance(&self.position, &next_waypoint);
-            let delta =
-                get_delta(ctx).as_secs() as f32 + get_delta(ctx).subsec_millis() as f32 / 1000.;
-
             let mut translation = na::normalize(&(next_waypoint - current_waypoint))
-                * Self::SPEED
-                * delta
+                * self.speed
+                * duration_to_f64(delta) as f32
                 * match (self.is_arriving(), self.is_leaving()) {
                     (true, _) => distance_to_next.powf(1.3).max(0.2),
                     (_, true) => (1. - distance_to_next).powf(1.3).max(0.2),
@@ -180,6 +241,8 @@ impl<'a> Updatable<'a> for Ship {
                             self.path = nb.clone();
                             self.next_waypoint().unwrap()
                         }
+                        // Turn around at either end of a route, and when heading
+                        // backwards on a loop so ships circulate forwards.
                         _ => {
                             self.reverse = !self.reverse;
                             self.next_waypoint()
@@ -187,7 +250,7 @@ impl<'a> Updatable<'a> for Ship {
                         }
                     };
                     // Set dock timer.
-                    self.docked = Duration::from_millis(999);
+                    self.docked = self.docking_time;
                 }
             }
             self.position += translation;
@@ -195,7 +258,7 @@ impl<'a> Updatable<'a> for Ship {
             // Reduce time remaining, setting to zero if underflow etc.
             self.docked = self
                 .docked
-                .checked_sub(get_delta(ctx))
+                .checked_sub(delta)
                 .unwrap_or(Duration::from_millis(0));
         }
     }
@@ -217,20 +280,24 @@ impl<'a> Drawable<'a> for Ship {
         let display_position =
             Point2::new(self.position.coords.x + 0.5, self.position.coords.y + 0.5);
 
-        let (r, g, b) = match self.cargo {
-            Some((_, color)) => color.rgb(),
+        // Show the color loaded first, as it is drawn as a single sprite.
+        let (r, g, b) = match self.cargo.entries().first() {
+            Some(entry) => entry.color.rgb(),
             None => (69, 55, 52),
         };
+        let (column, row) = self.class.sprite();
+        let scale = self.class.sprite_scale();
 
         vec![DrawParam {
             src: Rect::new(
-                3. * Self::TILE_SIZE,
-                2. * Self::TILE_SIZE,
+                column * Self::TILE_SIZE,
+                row * Self::TILE_SIZE,
                 Self::TILE_SIZE,
                 Self::TILE_SIZE,
             ),
             dest: display_position,
             rotation,
+            scale: Point2::new(scale, scale),
             offset: Point2::new(0.5, 0.5),
             color: Some(ggezColor::from_rgb(r, g, b)),
             ..Default::default()
@@ -2,12 +2,21 @@ use super::*;
 
 /// Manages the placement of ships on routes.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-pub struct ShipBuilder;
+pub struct ShipBuilder {
+    class: ShipClass,
+    settings: DifficultySettings,
+}
 
 impl ShipBuilder {
-    /// Create a new ship builder.
-    pub fn new() -> Self {
-        ShipBuilder {}
+    /// Create a new ship builder for a ship of the given class, sailing as
+    /// given by the difficulty settings.
+    pub fn new(class: ShipClass, settings: DifficultySettings) -> Self {
+        ShipBuilder { class, settings }
+    }
+
+    /// Returns the class of the ship being built.
+    pub fn class(&self) -> ShipClass {
+        self.class
     }
 
     /// Attempts to place a ship at the given position, consuming the ship builder.
@@ -20,7 +29,12 @@ impl ShipBuilder {
         if let Some((_, route)) = world.routes_mut().find(|(_, r)| r.contains(waypoint)) {
             println!("Placed ship on route!");
             let initial_path = route.path(waypoint);
-            route.add_ship(Ship::new(waypoint, initial_path));
+            route.add_ship(Ship::new(
+                self.class,
+                &self.settings,
+                waypoint,
+                initial_path,
+            ));
             None
         } else {
             Some(self)
@@ -32,14 +46,17 @@ impl<'a> Drawable<'a> for ShipBuilder {
     type Data = Point2; // Mouse position
 
     fn draw(&self, mouse: &Point2) -> Vec<DrawParam> {
+        let (column, row) = self.class.sprite();
+        let scale = self.class.sprite_scale();
         vec![DrawParam {
             src: Rect::new(
-                3. * Self::TILE_SIZE,
-                2. * Self::TILE_SIZE,
+                column * Self::TILE_SIZE,
+                row * Self::TILE_SIZE,
                 Self::TILE_SIZE,
                 Self::TILE_SIZE,
             ),
             dest: Point2::new(mouse.coords.x, mouse.coords.y),
+            scale: Point2::new(scale, scale),
             offset: Point2::new(0.5, 0.5),
             color: Some(ggezColor::from_rgb(69, 55, 52)),
             ..Default::default()
@@ -9,13 +9,17 @@ use ggez::{
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Shipyard {
     ships: usize,
+    /// Settings of the ships built, as given by the difficulty.
+    settings: DifficultySettings,
 }
 
 impl Shipyard {
-    /// Create a new shipyard.
-    pub fn new() -> Self {
+    /// Create a new shipyard with the initial ships of the difficulty
+    /// settings, more are granted as the session progresses.
+    pub fn new(settings: DifficultySettings) -> Self {
         Shipyard {
-            ships: 3, // TODO: Make this scale with progress.
+            ships: settings.initial_ships,
+            settings,
         }
     }
 
@@ -24,62 +28,117 @@ impl Shipyard {
         self.ships
     }
 
-    /// Return if there are any ships available.
-    pub fn is_available(&self) -> bool {
-        self.ships > 0
+    /// Return if there are enough ships available to build the given class.
+    pub fn is_available(&self, class: ShipClass) -> bool {
+        self.ships >= class.cost()
     }
 
-    /// Returns a shipbuilder if not capped on ships.
-    pub fn build(&mut self) -> Option<ShipBuilder> {
-        if self.is_available() {
-            self.ships -= 1;
-            Some(ShipBuilder::new())
+    /// Returns a shipbuilder of the given class if not capped on ships.
+    pub fn build(&mut self, class: ShipClass) -> Option<ShipBuilder> {
+        if self.is_available(class) {
+            self.ships -= class.cost();
+            Some(ShipBuilder::new(class, self.settings))
         } else {
             None
         }
     }
 
+    /// Adds the given number of new ships to the shipyard.
+    pub fn grant(&mut self, ships: usize) {
+        self.ships += ships;
+    }
+
+    /// Takes the given number of ships from the shipyard, returning false
+    /// if not enough are available.
+    pub fn take(&mut self, ships: usize) -> bool {
+        if self.ships >= ships {
+            self.ships -= ships;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Returns a builder to the shipyard.
     pub fn add_builder(&mut self, builder: ShipBuilder) {
-        self.ships += 1;
+        self.ships += builder.class().cost();
     }
 
     /// Returns a ship to the shipyard.
     pub fn add_ship(&mut self, ship: Ship) {
-        self.ships += 1;
+        self.ships += ship.class().cost();
     }
 
-    /// Draws the shipyard with ship count on screen.
-    /// Does not implement Drawable since it is unable to be drawn using a
-    /// spritebatch.
-    pub fn paint(&self, font: &Font, ctx: &mut Context, config: &Config) -> GameResult<()> {
+    /// Returns the area on screen of the button of each ship class.
+    /// Buttons are as wide as the cost of the class.
+    fn buttons(ctx: &Context, config: &Config) -> Vec<(ShipClass, Rect)> {
         let (window_width, _) = get_drawable_size(ctx);
         let cell_size = (config.scaling * window_width) as f32 / config.grid_width as f32;
+        let gap = 3.;
 
-        let text = Text::new(ctx, &format!("{:1?}: ", self.ships), &font)?;
-        let text_dims = text.get_dimensions();
-
-        let x_offset =
-            cell_size * (config.grid_width / 2) as f32 - (text_dims.right() + 2. * cell_size) / 2.;
+        let classes = ShipClass::values();
+        let total_width = classes.iter().map(|c| c.cost() as f32 + gap).sum::<f32>() - gap;
+        let mut x_offset = cell_size * ((config.grid_width / 2) as f32 - total_width / 2. + 1.);
         let y_offset = cell_size * (config.grid_height as f32 + 2.);
 
+        classes
+            .into_iter()
+            .map(|class| {
+                let width = cell_size * class.cost() as f32;
+                let button = Rect::new(x_offset, y_offset, width, cell_size);
+                x_offset += width + gap * cell_size;
+                (class, button)
+            }).collect()
+    }
+
+    /// Returns the ship class of the button at the given screen position, if any.
+    pub fn class_at(&self, ctx: &Context, config: &Config, position: Point2) -> Option<ShipClass> {
+        Shipyard::buttons(ctx, config)
+            .into_iter()
+            .find(|(_, button)| button.contains(position))
+            .map(|(class, _)| class)
+    }
+
+    /// Draws the shipyard with ship count and a button for each ship class
+    /// on screen, where classes too costly to build are outlined.
+    /// Does not implement Drawable since it is unable to be drawn using a
+    /// spritebatch.
+    pub fn paint(&self, font: &Font, ctx: &mut Context, config: &Config) -> GameResult<()> {
+        let buttons = Shipyard::buttons(ctx, config);
+
         set_color(ctx, ggezColor::from_rgb(69, 55, 52))?;
-        draw(
-            ctx,
-            &text,
-            Point2::new(x_offset as f32, y_offset - text_dims.top() / 2.),
-            0.,
-        )?;
-        rectangle(
-            ctx,
-            DrawMode::Fill,
-            Rect::new(
-                x_offset + text_dims.right(),
-                y_offset,
-                cell_size * 2.,
-                cell_size,
-            ),
-        )?;
+        let text = Text::new(ctx, &format!("{:1?}: ", self.ships), &font)?;
+        if let Some((_, first)) = buttons.first() {
+            draw(
+                ctx,
+                &text,
+                Point2::new(
+                    first.x - text.width() as f32,
+                    first.y + (first.h - text.height() as f32) / 2.,
+                ),
+                0.,
+            )?;
+        }
+        for (class, button) in buttons {
+            let mode = if self.is_available(class) {
+                DrawMode::Fill
+            } else {
+                DrawMode::Line(2.)
+            };
+            rectangle(ctx, mode, button)?;
+
+            // Name the class below the button.
+            let name = Text::new(ctx, class.name(), font)?;
+            draw(
+                ctx,
+                &name,
+                Point2::new(
+                    button.x + (button.w - name.width() as f32) / 2.,
+                    button.y + button.h * 1.2,
+                ),
+                0.,
+            )?;
+        }
 
         // Reset color to default (white).
         set_color(ctx, ggezColor::from_rgb(255, 255, 255))?;
@@ -0,0 +1,717 @@
+use rand::{rngs::StdRng, Rng};
+use std::{collections::HashSet, time::Duration};
+
+use animation::{Animation, AnimationType};
+use config::{Difficulty, DifficultySettings, GameMode};
+use draw::Drawable;
+use geometry::Position;
+use map::{generator::seeded_rng, MapData, MapError};
+use port::{is_valid_arrangement, Port};
+use progression::{Action, Progression, ProgressionState};
+use ship::Shipyard;
+use tally::Tally;
+use time::GameTimer;
+use update::Updatable;
+use world::World;
+
+/// Notable things happening during a simulation step, to be presented by
+/// the frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationEvent {
+    /// A port was opened or an event of the progression happened.
+    ProgressionStep,
+    /// The given number of ships were granted to the shipyard.
+    ShipsGranted(usize),
+    /// The given number of route lines were made available.
+    LinesGranted(usize),
+}
+
+/// Reasons for a game session to be over.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// No game time is remaining.
+    TimeUp,
+    /// The target score was reached.
+    TargetReached,
+    /// The port at the given position stayed overloaded for too long.
+    Overload(Position),
+}
+
+/// Returns the animation played when a port is opened or changes colors.
+fn port_changed_animation() -> Animation {
+    Animation::new(
+        Duration::new(1, 0),
+        AnimationType::PulseScale {
+            amplitude: 0.4,
+            rate: 1.,
+        },
+    )
+}
+
+/// Complete state of a simulation, as stored in save files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedSimulation {
+    world: World,
+    tally: Tally,
+    map: MapData,
+    difficulty: Difficulty,
+    /// Settings of the difficulty, kept to restart custom difficulties.
+    settings: DifficultySettings,
+    mode: GameMode,
+    seed: u64,
+    /// Seed of the random generator when saved, as the generator state
+    /// itself cannot be stored.
+    rng_seed: u64,
+    game_timer: GameTimer,
+    progression: Progression,
+    progression_state: ProgressionState,
+}
+
+/// Advances the game session without any dependency on a window or
+/// rendering, holding the world, score and game progression.
+pub struct Simulation {
+    world: World,
+    tally: Tally,
+    /// Map the simulation was started from.
+    map: MapData,
+    difficulty: Difficulty,
+    /// Settings of the difficulty, kept to restart custom difficulties.
+    settings: DifficultySettings,
+    mode: GameMode,
+    seed: u64,
+    rng: StdRng,
+    game_timer: GameTimer,
+    progression: Progression,
+    progression_state: ProgressionState,
+}
+
+impl Simulation {
+    /// Creates a new simulation of the given map, sampling any unspecified
+    /// port colors and using the progression of the map at the given
+    /// difficulty and its settings. Timed sessions last the session length
+    /// of the map unless the mode gives one. All randomness is given by the
+    /// seed.
+    pub fn from_map(
+        map: MapData,
+        difficulty: Difficulty,
+        settings: DifficultySettings,
+        mode: GameMode,
+        seed: u64,
+    ) -> Result<Self, MapError> {
+        let session_length = match mode {
+            GameMode::Timed(length) => Some(Duration::from_secs(
+                length.unwrap_or(map.metadata.session_length),
+            )),
+            _ => None,
+        };
+        let progression = map.progression().for_difficulty(difficulty, &settings);
+        let mut rng = seeded_rng(seed);
+
+        // The world trades the colors of the progression at the difficulty.
+        let mut world_map = map.clone();
+        world_map.progression = Some(progression.clone());
+        let mut world = world_map.into_world(&mut rng)?;
+        *world.shipyard_mut() = Shipyard::new(settings);
+        Ok(Simulation {
+            tally: Tally::new(world.colors()),
+            world,
+            map,
+            difficulty,
+            settings,
+            mode,
+            seed,
+            rng,
+            game_timer: GameTimer::new(session_length),
+            progression_state: ProgressionState::new(&progression),
+            progression,
+        })
+    }
+
+    /// Creates a new simulation of the same map and seed, as it was when
+    /// started.
+    pub fn restart(&self) -> Result<Self, MapError> {
+        Simulation::from_map(
+            self.map.clone(),
+            self.difficulty,
+            self.settings,
+            self.mode,
+            self.seed,
+        )
+    }
+
+    /// Restores a simulation from its saved state.
+    pub fn restore(saved: SavedSimulation) -> Self {
+        Simulation {
+            world: saved.world,
+            tally: saved.tally,
+            map: saved.map,
+            difficulty: saved.difficulty,
+            settings: saved.settings,
+            mode: saved.mode,
+            seed: saved.seed,
+            rng: seeded_rng(saved.rng_seed),
+            game_timer: saved.game_timer,
+            progression: saved.progression,
+            progression_state: saved.progression_state,
+        }
+    }
+
+    /// Returns the complete state of the simulation for saving.
+    /// The random generator is reseeded, so that the simulation continues
+    /// identically whether restored or not.
+    pub fn save(&mut self) -> SavedSimulation {
+        let rng_seed = self.rng.gen();
+        self.rng = seeded_rng(rng_seed);
+        SavedSimulation {
+            world: self.world.clone(),
+            tally: self.tally.clone(),
+            map: self.map.clone(),
+            difficulty: self.difficulty,
+            settings: self.settings,
+            mode: self.mode,
+            seed: self.seed,
+            rng_seed,
+            game_timer: self.game_timer.clone(),
+            progression: self.progression.clone(),
+            progression_state: self.progression_state.clone(),
+        }
+    }
+
+    /// Returns the title of the simulated map.
+    pub fn map_title(&self) -> &str {
+        &self.map.metadata.title
+    }
+
+    /// Returns the difficulty of the simulation.
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Returns the settings of the difficulty of the simulation.
+    pub fn difficulty_settings(&self) -> DifficultySettings {
+        self.settings
+    }
+
+    /// Returns the game mode of the simulation.
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Returns the seed of the simulation.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns a reference to the simulated world.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Returns a mutable reference to the simulated world.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Returns a reference to the current tally.
+    pub fn tally(&self) -> &Tally {
+        &self.tally
+    }
+
+    /// Returns a reference to the game timer.
+    pub fn game_timer(&self) -> &GameTimer {
+        &self.game_timer
+    }
+
+    /// Returns the reason the game session is over, if it is, as given by
+    /// the game mode.
+    pub fn end_reason(&self) -> Option<EndReason> {
+        if self.mode == GameMode::Zen {
+            return None;
+        }
+        if let Some(port) = self.world.ports().iter().find(|p| p.has_overflowed()) {
+            return Some(EndReason::Overload(port.position()));
+        }
+        match self.mode {
+            GameMode::Timed(_) if self.game_timer.has_game_ended() => Some(EndReason::TimeUp),
+            GameMode::TargetScore(target) if self.tally.score() >= target => {
+                Some(EndReason::TargetReached)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the game session is over.
+    pub fn is_over(&self) -> bool {
+        self.end_reason().is_some()
+    }
+
+    /// Advances the simulation by the given amount of game time, returning
+    /// all events which occured.
+    pub fn tick(&mut self, delta: Duration) -> Vec<SimulationEvent> {
+        let mut events = vec![];
+        self.game_timer.update(delta, ());
+
+        self.update_ships(delta);
+
+        // Update all port stockpiles, demands and animations.
+        let connected = self
+            .world
+            .routes()
+            .flat_map(|(_, route)| route.ports().cloned())
+            .collect::<HashSet<_>>();
+        for port in self.world.ports_mut() {
+            port.update(delta, connected.contains(&port.position()));
+            *port.animation_mut() = match port.animation_mut() {
+                Some(ref mut animation) => {
+                    animation.update(delta, ());
+                    if animation.has_finished() {
+                        None
+                    } else {
+                        Some(*animation)
+                    }
+                }
+                None => None,
+            }
+        }
+
+        // Open all ports scheduled by the map.
+        let elapsed = self.game_timer.time_elapsed();
+        let opened_ports = self.world.open_scheduled_ports(elapsed);
+        if !opened_ports.is_empty() {
+            events.push(SimulationEvent::ProgressionStep);
+        }
+        for port in self.world.ports_mut() {
+            if opened_ports.contains(&port.position()) {
+                *port.animation_mut() = Some(port_changed_animation());
+            }
+        }
+        for position in opened_ports {
+            self.trade_all_colors(position);
+        }
+
+        // Take the actions of all progression events which are due.
+        let due_actions = self
+            .progression_state
+            .advance(
+                &self.progression,
+                elapsed,
+                self.tally.score(),
+                self.world.ports().len(),
+            ).into_iter()
+            .map(|event| event.actions.clone())
+            .collect::<Vec<_>>();
+        for actions in due_actions {
+            events.push(SimulationEvent::ProgressionStep);
+            for action in actions {
+                events.extend(self.apply_action(action));
+            }
+        }
+        events
+    }
+
+    /// Applies an action of the progression to the session, returning any
+    /// event to present other than the progression step itself.
+    fn apply_action(&mut self, action: Action) -> Option<SimulationEvent> {
+        match action {
+            Action::OpenPort(position) => {
+                let mut opened_new_port = false;
+                if let Some(port) = self.world.open_port(position) {
+                    *port.animation_mut() = Some(port_changed_animation());
+                    opened_new_port = true;
+                }
+                if opened_new_port {
+                    self.trade_all_colors(position);
+                }
+            }
+            Action::OpenRandomPort => {
+                // Ugly variable to avoid NLL not being available in stable yet.
+                let mut opened_new_port = None;
+
+                // If we have more ports to add, do so and animate.
+                if let Some(new_port) = self.world.open_random_port(&mut self.rng) {
+                    *new_port.animation_mut() = Some(port_changed_animation());
+                    opened_new_port = Some(new_port.position());
+                }
+                match opened_new_port {
+                    Some(position) => self.trade_all_colors(position),
+                    None => self.update_port_colors(None),
+                }
+            }
+            Action::ChangePortColors(position) => self.update_port_colors(position),
+            Action::GrantShips(ships) => {
+                self.world.shipyard_mut().grant(ships);
+                return Some(SimulationEvent::ShipsGranted(ships));
+            }
+            Action::GrantLines(lines) => {
+                let added = self.world.add_lines(lines);
+                if added > 0 {
+                    return Some(SimulationEvent::LinesGranted(added));
+                }
+            }
+            Action::AddColor(color) => {
+                if self.world.add_color(color) {
+                    self.tally.add_color(color);
+                    self.update_port_colors(None);
+                }
+            }
+        }
+        None
+    }
+
+    /// Moves all ships along their routes, loading and unloading cargo at
+    /// ports and adding delivered cargo to the tally.
+    fn update_ships(&mut self, delta: Duration) {
+        // Stock and demand left of each port, as ships trade during the step.
+        let mut tradings = self
+            .world
+            .ports()
+            .iter()
+            .map(|p| (p.position(), p.import(), p.export(), p.stock(), p.demand()))
+            .collect::<Vec<_>>();
+        let mut new_colors = vec![];
+
+        for (_, route) in self.world.routes_mut() {
+            let next_paths = route
+                .ships()
+                .map(|s| (s.reverse(), s.is_arriving(), s.next_waypoint().unwrap()))
+                .map(|(reverse, is_arriving, curr)| {
+                    if is_arriving && !reverse {
+                        route.next_path(Position::from(curr))
+                    } else if is_arriving && reverse {
+                        route.previous_path(Position::from(curr))
+                    } else {
+                        None
+                    }
+                }).collect::<Vec<_>>();
+            next_paths
+                .into_iter()
+                .zip(route.ships_mut())
+                .for_each(|(path, ship)| {
+                    ship.update(delta, path);
+                });
+
+            for ship in route.ships_mut() {
+                // Remove all animations that have finished.
+                *ship.animation_mut() = match ship.animation_mut() {
+                    Some(ref mut animation) => {
+                        animation.update(delta, ());
+                        if animation.has_finished() {
+                            None
+                        } else {
+                            Some(*animation)
+                        }
+                    }
+                    None => None,
+                };
+
+                if ship.is_docked() {
+                    // Animate during about half of the docking time.
+                    let mut animation_length = ship.docking_time() / 2;
+                    // TODO: Quick fix, We have no cargo, extend loading animation.
+                    if ship.cargo().is_empty() {
+                        animation_length *= 2;
+                    }
+                    let (_, import, export, stock, demand) = tradings
+                        .iter_mut()
+                        .find(|(p, _, _, _, _)| *p == Position::from(ship.position()))
+                        .expect("No port at ship dock");
+
+                    // Unload cargo meeting the port demand, adding to score,
+                    // otherwise fill the hold from the port stockpile.
+                    let shown_color = ship.cargo().entries().first().map(|entry| entry.color);
+                    let unloaded = ship.try_unload(*import, *demand);
+                    *demand -= unloaded;
+                    if unloaded > 0 {
+                        new_colors.push((*import, unloaded));
+                    }
+                    let loaded = if unloaded == 0 {
+                        ship.try_load(*export, *stock)
+                    } else {
+                        0
+                    };
+                    *stock -= loaded;
+
+                    // Add cargo animation if already not animated.
+                    if (unloaded > 0 || loaded > 0) && ship.animation().is_none() {
+                        *ship.animation_mut() = Some(Animation::new(
+                            animation_length,
+                            AnimationType::ColorDrain {
+                                from: shown_color,
+                                to: ship.cargo().entries().first().map(|entry| entry.color),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Take what was traded from the ports.
+        for (port, (_, _, _, stock, demand)) in self.world.ports_mut().iter_mut().zip(tradings) {
+            let (taken, met) = (port.stock() - stock, port.demand() - demand);
+            port.take_stock(taken);
+            port.meet_demand(met);
+        }
+
+        // Add score for all colors collected.
+        for (color, amount) in new_colors {
+            self.tally.update(color, amount);
+        }
+    }
+
+    /// Changes the colors of the newly opened port at the given position,
+    /// and random ports until valid, if not all colors are traded yet.
+    /// Sessions may trade more colors than ports open at the start.
+    fn trade_all_colors(&mut self, opened: Position) {
+        if !is_valid_arrangement(self.world.ports(), self.world.colors()) {
+            self.update_port_colors(Some(opened));
+        }
+    }
+
+    /// Updates the port colors by switching the open port at the given
+    /// position (or a random port), and then random ports until valid.
+    /// Nothing is changed if there are too few open ports to trade all colors.
+    fn update_port_colors(&mut self, position: Option<Position>) {
+        let colors = self.world.colors().to_vec();
+        let ports = self.world.ports_mut();
+        if ports.len() < colors.len() {
+            return;
+        }
+        let mut first_index = match position {
+            Some(position) => match ports.iter().position(|p| p.position() == position) {
+                Some(index) => Some(index),
+                None => return,
+            },
+            None => None,
+        };
+        loop {
+            // Extra brackes due to NLL not existing in stable Rust yet.
+            {
+                let index = match first_index {
+                    Some(index) => index,
+                    None => self.rng.gen_range(0, ports.len()),
+                };
+                let port = &mut ports[index];
+                let (import, export) = Port::sample_colors(&mut self.rng, &colors);
+
+                // Update if we got new colors.
+                if port.import() != import || port.export() != export {
+                    // Got valid colors
+                    *port.import_mut() = import;
+                    *port.export_mut() = export;
+                    *port.animation_mut() = Some(port_changed_animation());
+                    first_index = None;
+                } else {
+                    continue;
+                }
+            }
+            if is_valid_arrangement(ports, &colors) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use route::{RouteShape, Waypoint};
+    use ship::ShipClass;
+
+    /// Starts a simulation of a strait with the given ports, as given to
+    /// `MapData::strait`, at normal difficulty.
+    fn simulation(ports: &[(&str, &str, u64)], mode: GameMode) -> Simulation {
+        simulation_with(ports, DifficultySettings::default(), mode)
+    }
+
+    /// Starts a simulation of a strait with the given ports and settings.
+    fn simulation_with(
+        ports: &[(&str, &str, u64)],
+        settings: DifficultySettings,
+        mode: GameMode,
+    ) -> Simulation {
+        Simulation::from_map(
+            MapData::strait(ports),
+            Difficulty::Normal,
+            settings,
+            mode,
+            1,
+        ).unwrap()
+    }
+
+    /// Advances the simulation at 60 steps per second for the given number
+    /// of seconds or until it is over, returning all events.
+    fn run(simulation: &mut Simulation, seconds: u64) -> Vec<SimulationEvent> {
+        let mut events = vec![];
+        for _ in 0..seconds * 60 {
+            events.extend(simulation.tick(Duration::from_secs(1) / 60));
+            if simulation.is_over() {
+                break;
+            }
+        }
+        events
+    }
+
+    /// Links the first two ports by a route with a ship on it.
+    fn link_first_ports(simulation: &mut Simulation) {
+        let ports = simulation
+            .world()
+            .ports()
+            .iter()
+            .map(|p| p.position())
+            .collect::<Vec<_>>();
+        let (_, path) = simulation.world().route(ports[0], ports[1]).unwrap();
+        let shape = RouteShape::values()[0];
+        let world = simulation.world_mut();
+        world.add_route(
+            shape,
+            ports[0],
+            ports[1],
+            path.into_iter().map(Waypoint::from).collect(),
+        );
+        let builder = world.shipyard_mut().build(ShipClass::values()[0]).unwrap();
+        assert!(builder.try_place(ports[0], world).is_none());
+    }
+
+    #[test]
+    fn opens_scheduled_ports() {
+        let mut simulation = simulation(
+            &[("Blue", "Red", 0), ("Red", "Blue", 0), ("Green", "Red", 5)],
+            GameMode::Endless,
+        );
+        assert_eq!(simulation.world().ports().len(), 2);
+        assert!(run(&mut simulation, 4).is_empty());
+        assert_eq!(simulation.world().ports().len(), 2);
+        let events = run(&mut simulation, 2);
+        assert_eq!(simulation.world().ports().len(), 3);
+        assert!(events.contains(&SimulationEvent::ProgressionStep));
+    }
+
+    #[test]
+    fn ends_when_time_is_up() {
+        let mut simulation = simulation(
+            &[("Blue", "Red", 0), ("Red", "Blue", 0)],
+            GameMode::Timed(Some(3)),
+        );
+        run(&mut simulation, 2);
+        assert_eq!(simulation.end_reason(), None);
+        run(&mut simulation, 2);
+        assert_eq!(simulation.end_reason(), Some(EndReason::TimeUp));
+    }
+
+    #[test]
+    fn ends_when_target_is_reached() {
+        // The score counts the color collected least, so trade only two.
+        let settings = DifficultySettings {
+            colors: 2,
+            ..DifficultySettings::default()
+        };
+        let mut simulation = simulation_with(
+            &[("Blue", "Green", 0), ("Green", "Blue", 0)],
+            settings,
+            GameMode::TargetScore(1),
+        );
+        link_first_ports(&mut simulation);
+        assert_eq!(simulation.world().colors().len(), 2);
+        run(&mut simulation, 60);
+        assert_eq!(simulation.end_reason(), Some(EndReason::TargetReached));
+        assert!(simulation.tally().score() >= 1);
+    }
+
+    #[test]
+    fn ends_when_port_overflows() {
+        let mut simulation =
+            simulation(&[("Blue", "Red", 0), ("Red", "Blue", 0)], GameMode::Endless);
+        run(&mut simulation, 30);
+        assert_eq!(simulation.end_reason(), None);
+        run(&mut simulation, 120);
+        let position = match simulation.end_reason() {
+            Some(EndReason::Overload(position)) => position,
+            reason => panic!("unexpected end reason: {:?}", reason),
+        };
+        assert!(simulation.world().port(position).unwrap().has_overflowed());
+    }
+
+    #[test]
+    fn served_ports_do_not_overload() {
+        let settings = DifficultySettings {
+            colors: 2,
+            ..DifficultySettings::default()
+        };
+        let mut simulation = simulation_with(
+            &[("Blue", "Green", 0), ("Green", "Blue", 0)],
+            settings,
+            GameMode::Endless,
+        );
+        link_first_ports(&mut simulation);
+        for _ in 0..300 {
+            run(&mut simulation, 1);
+            assert!(simulation
+                .world()
+                .ports()
+                .iter()
+                .all(|p| !p.is_overloaded()));
+        }
+        assert_eq!(simulation.end_reason(), None);
+    }
+
+    #[test]
+    fn zen_never_ends() {
+        let mut simulation = simulation(&[("Blue", "Red", 0), ("Red", "Blue", 0)], GameMode::Zen);
+        run(&mut simulation, 150);
+        assert!(simulation
+            .world()
+            .ports()
+            .iter()
+            .any(|p| p.has_overflowed()));
+        assert_eq!(simulation.end_reason(), None);
+    }
+
+    #[test]
+    fn circulates_ships_on_loops() {
+        let mut simulation = simulation(
+            &[
+                ("Blue", "Red", 0),
+                ("Red", "Green", 0),
+                ("Green", "Blue", 0),
+            ],
+            GameMode::Zen,
+        );
+        let ports = simulation
+            .world()
+            .ports()
+            .iter()
+            .map(|p| p.position())
+            .collect::<Vec<_>>();
+        let shape = RouteShape::values()[0];
+        for (start, end) in vec![(0, 1), (1, 2), (2, 0)] {
+            let (_, path) = simulation.world().route(ports[start], ports[end]).unwrap();
+            simulation.world_mut().add_route(
+                shape,
+                ports[start],
+                ports[end],
+                path.into_iter().map(Waypoint::from).collect(),
+            );
+        }
+        let world = simulation.world_mut();
+        let builder = world.shipyard_mut().build(ShipClass::values()[0]).unwrap();
+        assert!(builder.try_place(ports[0], world).is_none());
+
+        // The ship never turns around, calling at every port in order.
+        let mut visited = vec![];
+        for _ in 0..60 * 60 {
+            simulation.tick(Duration::from_secs(1) / 60);
+            let (_, route) = simulation.world().routes().next().unwrap();
+            let ship = route.ships().next().unwrap();
+            assert!(!ship.reverse());
+            let position = Position::from(ship.position());
+            if ports.contains(&position) && visited.last() != Some(&position) {
+                visited.push(position);
+            }
+        }
+        let index = |position: &Position| ports.iter().position(|p| p == position).unwrap();
+        assert!(visited.len() > ports.len());
+        for pair in visited.windows(2) {
+            assert_eq!(index(&pair[1]), (index(&pair[0]) + 1) % ports.len());
+        }
+    }
+}
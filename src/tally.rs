@@ -5,7 +5,6 @@ use ggez::{
     },
     Context, GameResult,
 };
-use std::iter::FromIterator;
 
 use color::Color;
 use config::Config;
@@ -14,43 +13,75 @@ use config::Config;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Tally {
     collected: Vec<(Color, u32)>,
+    /// Score already reached when each color was added, counted towards the
+    /// score but not shown as collected.
+    #[serde(default)]
+    baselines: Vec<(Color, u32)>,
 }
 
 impl Tally {
-    /// Creates a new tally.
-    pub fn new() -> Self {
+    /// Creates a new tally of the given colors.
+    pub fn new(colors: &[Color]) -> Self {
         Tally {
-            collected: Vec::from_iter(Color::values().into_iter().map(|c| (c, 0))),
+            collected: colors.iter().map(|c| (*c, 0)).collect(),
+            baselines: vec![],
         }
     }
 
-    /// Returns the current score.
+    /// Starts keeping track of the given color, if not already done.
+    /// Nothing of the color is collected yet, but the current score is kept
+    /// as its baseline so that the score does not drop.
+    pub fn add_color(&mut self, color: Color) {
+        if !self.collected.iter().any(|(c, _)| *c == color) {
+            let score = self.score();
+            self.collected.push((color, 0));
+            self.baselines.push((color, score));
+        }
+    }
+
+    /// Returns the current score, the least collected of any color counting
+    /// from its baseline.
     pub fn score(&self) -> u32 {
         self.collected
             .iter()
-            .map(|(_, amount)| *amount)
+            .map(|(color, amount)| amount + self.baseline(*color))
             .min()
             .expect("No score found")
     }
 
-    /// Returns the amount collected for the given color.
+    /// Returns the score reached when the given color was added, zero for
+    /// colors kept track of from the start.
+    fn baseline(&self, color: Color) -> u32 {
+        self.baselines
+            .iter()
+            .find(|(c, _)| *c == color)
+            .map_or(0, |(_, score)| *score)
+    }
+
+    /// Returns the amount collected of each color kept track of, in the
+    /// order they were added.
+    pub fn entries(&self) -> &[(Color, u32)] {
+        &self.collected
+    }
+
+    /// Returns the amount collected for the given color, zero if the
+    /// color is not kept track of.
     pub fn get(&self, color: Color) -> u32 {
         self.collected
             .iter()
             .find(|(c, _)| *c == color)
-            .map(|(_, amount)| *amount)
-            .expect("Tally for color not found")
+            .map_or(0, |(_, amount)| *amount)
     }
 
-    /// Increments the tally for the given color.
-    pub fn update(&mut self, color: Color) {
+    /// Increments the tally for the given color by the given amount.
+    pub fn update(&mut self, color: Color, delivered: u32) {
         {
             let (_, amount) = self
                 .collected
                 .iter_mut()
                 .find(|(c, _)| *c == color)
                 .expect("Tally for color not found");
-            *amount += 1;
+            *amount += delivered;
         }
 
         // TODO: Remove this pln.
@@ -63,10 +94,12 @@ impl Tally {
     pub fn paint(&self, font: &Font, ctx: &mut Context, _config: &Config) -> GameResult<()> {
         // TODO: Properly ugly code with loads of magic constants.
         let color_size = 48.;
-        let segment_size = 300.;
 
+        // Narrow the segments when many colors are traded.
         let (window_width, _) = get_drawable_size(ctx);
-        let x_offset = window_width as f32 / 2. - 1.3 * segment_size;
+        let num_colors = self.collected.len() as f32;
+        let segment_size = (window_width as f32 * 0.8 / num_colors).min(300.);
+        let x_offset = window_width as f32 / 2. - (num_colors / 2. - 0.2) * segment_size;
         let y_offset = 60.;
 
         // Draw score for each color.
@@ -97,3 +130,29 @@ impl Tally {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn added_color_starts_empty_and_keeps_score() {
+        let (blue, red, green) = (
+            Color::from_name("Blue").unwrap(),
+            Color::from_name("Red").unwrap(),
+            Color::from_name("Green").unwrap(),
+        );
+        let mut tally = Tally::new(&[blue, red]);
+        tally.update(blue, 5);
+        tally.update(red, 3);
+        tally.add_color(green);
+        assert_eq!(tally.get(green), 0);
+        assert_eq!(tally.score(), 3);
+
+        tally.update(red, 4);
+        assert_eq!(tally.score(), 3);
+        tally.update(green, 2);
+        assert_eq!(tally.score(), 5);
+        assert_eq!(tally.entries(), &[(blue, 5), (red, 7), (green, 2)][..]);
+    }
+}
@@ -1,40 +1,51 @@
 use ggez::{
     graphics::{draw, get_drawable_size, set_color, Color, Font, Point2, Text},
-    timer::get_time_since_start,
     Context, GameResult,
 };
 use std::time::Duration;
 
 use config::Config;
+use update::Updatable;
 
-/// Keeps track of the time past since the game was started.
+/// Keeps track of the game time past since the session was started.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameTimer {
-    start_time: Duration,
-    session_length: Duration,
+    elapsed: Duration,
+    /// Length of the session, if limited.
+    session_length: Option<Duration>,
 }
 
 impl GameTimer {
-    /// Creates a new timer with the given start time and game session length.
-    pub fn new(start_time: Duration, session_length: Duration) -> Self {
+    /// Creates a new timer with the given game session length, counting
+    /// up without end if not given.
+    pub fn new(session_length: Option<Duration>) -> Self {
         GameTimer {
-            start_time,
+            elapsed: Duration::from_secs(0),
             session_length,
         }
     }
 
-    /// Returns the remaining game time.
-    pub fn time_remaining(&self, ctx: &Context) -> Duration {
-        (self.start_time + self.session_length)
-            .checked_sub(get_time_since_start(ctx))
-            .unwrap_or(Duration::from_secs(0))
+    /// Returns the game time elapsed since the start.
+    pub fn time_elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the remaining game time, if the session length is limited.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.session_length.map(|session_length| {
+            session_length
+                .checked_sub(self.elapsed)
+                .unwrap_or(Duration::from_secs(0))
+        })
     }
 
     /// Returns true if there is no game time remaining.
-    pub fn has_game_ended(&self, ctx: &Context) -> bool {
-        self.time_remaining(ctx).as_secs() == 0
+    pub fn has_game_ended(&self) -> bool {
+        self.time_remaining().map(|remaining| remaining.as_secs()) == Some(0)
     }
 
-    /// Draws the current remaining game time on screen.
+    /// Draws the current remaining game time on screen, or the time elapsed
+    /// if the session length is not limited.
     /// Does not implement Drawable since it is unable to be drawn using a
     /// spritebatch.
     pub fn paint(&self, font: &Font, ctx: &mut Context, config: &Config) -> GameResult<()> {
@@ -44,13 +55,13 @@ impl GameTimer {
 
         let y_offset = (config.grid_height as f32 + 1.) as f32 * cell_size;
 
-        let seconds_remaining = self.time_remaining(ctx).as_secs();
+        let seconds = self.time_remaining().unwrap_or(self.elapsed).as_secs();
 
         // Draw remaining time.
         set_color(ctx, Color::from_rgb(69, 55, 52))?;
         let text = Text::new(
             ctx,
-            &format!("{}:{:#02}", seconds_remaining / 60, seconds_remaining % 60),
+            &format!("{}:{:#02}", seconds / 60, seconds % 60),
             &font,
         )?;
         draw(
@@ -68,3 +79,11 @@ impl GameTimer {
         Ok(())
     }
 }
+
+impl<'a> Updatable<'a> for GameTimer {
+    type Data = ();
+
+    fn update(&'a mut self, delta: Duration, _data: ()) {
+        self.elapsed += delta;
+    }
+}
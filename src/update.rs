@@ -1,4 +1,4 @@
-use ggez::Context;
+use std::time::Duration;
 
 /// An updatable type, which internal data needs to be updated based on
 /// game time etc.
@@ -6,6 +6,7 @@ pub trait Updatable<'a> {
     /// Environmental data needed to update.
     type Data;
 
-    /// Updates the internal data of the type.
-    fn update(&'a mut self, ctx: &'a Context, data: Self::Data);
+    /// Updates the internal data of the type, advancing it by the given
+    /// amount of game time.
+    fn update(&'a mut self, delta: Duration, data: Self::Data);
 }
@@ -1,54 +1,177 @@
 use rand::Rng;
 
+use color::Color;
+use config::DifficultySettings;
 use geometry::Position;
 use port::Port;
-use route::{find_path, reachable, Route, RouteShape, Waypoint};
+use route::{
+    find_path, LinkRemoval, NavigationGrid, Route, RouteLayout, RouteShape, Waypoint, DEFAULT_LINES,
+};
 use ship::{Ship, Shipyard};
-use std::{collections::HashMap, iter::FromIterator};
-use tile::Tile;
+use std::{
+    collections::{HashMap, HashSet},
+    iter::FromIterator,
+    time::Duration,
+};
+use tile::{Tile, TileKind};
 
 /// Holds all information on the game world.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct World {
+    /// Tiles in row-major order.
     map: Vec<Tile>,
+    width: u32,
+    height: u32,
+    navigation: NavigationGrid,
     open_ports: Vec<Port>,
+    /// Index into the open ports by position.
+    #[serde(with = "::geometry::serde_position_map")]
+    port_index: HashMap<Position, usize>,
     closed_ports: Vec<Port>,
+    #[serde(with = "::geometry::serde_position_map")]
+    port_names: HashMap<Position, String>,
+    port_schedule: Vec<(Duration, Position)>,
     routes: HashMap<RouteShape, Route>,
     shipyard: Shipyard,
+    /// Colors currently being traded.
+    colors: Vec<Color>,
+    /// Route lines which can be drawn.
+    #[serde(default = "default_lines")]
+    lines: Vec<RouteShape>,
+}
+
+/// Returns the lines available from the start of sessions without their
+/// own progression.
+fn default_lines() -> Vec<RouteShape> {
+    RouteShape::values()
+        .into_iter()
+        .take(DEFAULT_LINES)
+        .collect()
 }
 
 impl World {
     /// Creates a new world.
+    /// The grid size is given by the tile furthest from origo, and any
+    /// positions without a given tile are filled with land.
     pub fn new<I1, I2>(tiles: I1, open_ports: I2, closed_ports: I2) -> Self
     where
         I1: Iterator<Item = Tile>,
         I2: Iterator<Item = Port>,
     {
+        let tiles = Vec::from_iter(tiles);
+        let width = tiles.iter().map(|t| t.position().x + 1).max().unwrap_or(0) as u32;
+        let height = tiles.iter().map(|t| t.position().y + 1).max().unwrap_or(0) as u32;
+
+        // Place all tiles in row-major order.
+        let mut map = (0..width * height)
+            .map(|i| {
+                let position = Position::new((i % width) as i32, (i / width) as i32);
+                Tile::new(position, TileKind::Land)
+            }).collect::<Vec<_>>();
+        for tile in tiles {
+            let position = tile.position();
+            if position.x >= 0 && position.y >= 0 {
+                map[(position.y as u32 * width + position.x as u32) as usize] = tile;
+            }
+        }
+
+        let open_ports = Vec::from_iter(open_ports);
+        let port_index = open_ports
+            .iter()
+            .enumerate()
+            .map(|(i, port)| (port.position(), i))
+            .collect();
         World {
-            map: Vec::from_iter(tiles),
-            open_ports: Vec::from_iter(open_ports),
+            navigation: NavigationGrid::new(&map, &open_ports),
+            map,
+            width,
+            height,
+            open_ports,
+            port_index,
             closed_ports: Vec::from_iter(closed_ports),
+            port_names: HashMap::new(),
+            port_schedule: vec![],
             routes: HashMap::new(),
-            shipyard: Shipyard::new(),
+            shipyard: Shipyard::new(DifficultySettings::default()),
+            colors: vec![],
+            lines: vec![],
         }
     }
 
     /// Opens a random closed port (if any is left), returning a mutable reference
-    /// to the opened port.
+    /// to the opened port. Ports scheduled to open at a given time are skipped.
     pub fn open_random_port<R: Rng>(&mut self, gen: &mut R) -> Option<&mut Port> {
-        gen.shuffle(&mut self.closed_ports);
-        if let Some(port) = self.closed_ports.pop() {
-            self.open_ports.push(port);
-            let open_ports_len = self.open_ports.len();
+        let candidates = self
+            .closed_ports
+            .iter()
+            .map(|port| port.position())
+            .filter(|position| !self.port_schedule.iter().any(|(_, p)| p == position))
+            .collect::<Vec<_>>();
+        match gen.choose(&candidates) {
+            Some(position) => self.open_port(*position),
+            None => None,
+        }
+    }
 
-            // Get mutable reference to last element.
-            self.open_ports.get_mut(open_ports_len - 1)
-        } else {
-            None
+    /// Schedules the closed port at the given position to open after the
+    /// given time into the session.
+    pub fn schedule_port(&mut self, time: Duration, position: Position) {
+        self.port_schedule.push((time, position));
+        self.port_schedule.sort_by_key(|(time, _)| *time);
+    }
+
+    /// Opens all scheduled ports due at the given time into the session,
+    /// returning their positions.
+    pub fn open_scheduled_ports(&mut self, elapsed: Duration) -> Vec<Position> {
+        let due = self
+            .port_schedule
+            .iter()
+            .take_while(|(time, _)| *time <= elapsed)
+            .count();
+        let mut opened = vec![];
+        for (_, position) in self.port_schedule.drain(..due).collect::<Vec<_>>() {
+            if self.open_port(position).is_some() {
+                opened.push(position);
+            }
         }
+        opened
+    }
+
+    /// Opens the closed port at the given position (if any), returning a
+    /// mutable reference to the opened port.
+    pub fn open_port(&mut self, position: Position) -> Option<&mut Port> {
+        let index = self
+            .closed_ports
+            .iter()
+            .position(|port| port.position() == position)?;
+        let port = self.closed_ports.remove(index);
+        self.navigation.set_passable(position, true);
+        self.port_index.insert(position, self.open_ports.len());
+        self.open_ports.push(port);
+        self.open_ports.last_mut()
+    }
+
+    /// Sets the name of the port at the given position.
+    pub fn name_port(&mut self, position: Position, name: String) {
+        self.port_names.insert(position, name);
+    }
+
+    /// Returns the name of the port at the given position, if it has one.
+    pub fn port_name(&self, position: Position) -> Option<&str> {
+        self.port_names.get(&position).map(|name| name.as_str())
     }
 
-    /// Returns a slice over all map tiles and their position.
+    /// Returns the width of the map in tiles.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the map in tiles.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns a slice over all map tiles and their position, in row-major order.
     pub fn tiles(&self) -> &[Tile] {
         &self.map
     }
@@ -73,6 +196,99 @@ impl World {
         self.routes.iter_mut()
     }
 
+    /// Returns the layout of all routes, with their paths and ships.
+    pub fn route_layouts(&self) -> HashMap<RouteShape, RouteLayout> {
+        self.routes
+            .iter()
+            .map(|(shape, route)| (*shape, route.layout()))
+            .collect()
+    }
+
+    /// Restores all routes to the given layouts, moving ships between the
+    /// routes and the shipyard as needed. Ships are told apart by identity,
+    /// see `Route::restore`. Returns false, leaving the routes unchanged, if
+    /// the shipyard does not have the ships needed.
+    pub fn restore_routes(&mut self, layouts: HashMap<RouteShape, RouteLayout>) -> bool {
+        let current = self
+            .routes
+            .values()
+            .flat_map(|route| route.ships())
+            .map(|ship| ship.id())
+            .collect::<HashSet<_>>();
+        let restored = layouts
+            .values()
+            .flat_map(|layout| layout.ships())
+            .map(|ship| ship.id())
+            .collect::<HashSet<_>>();
+        let added = layouts
+            .values()
+            .flat_map(|layout| layout.ships())
+            .filter(|ship| !current.contains(&ship.id()))
+            .map(|ship| ship.class().cost())
+            .sum::<usize>();
+        let removed = self
+            .routes
+            .values()
+            .flat_map(|route| route.ships())
+            .filter(|ship| !restored.contains(&ship.id()))
+            .map(|ship| ship.class().cost())
+            .sum::<usize>();
+        if added > removed {
+            if !self.shipyard.take(added - removed) {
+                return false;
+            }
+        } else {
+            self.shipyard.grant(removed - added);
+        }
+
+        let mut ships = self
+            .routes
+            .values_mut()
+            .flat_map(|route| route.remove_ships())
+            .collect::<Vec<_>>();
+        let mut routes = HashMap::new();
+        for (shape, layout) in layouts {
+            let mut route = self.routes.remove(&shape).unwrap_or_else(Route::new);
+            route.restore(layout, &mut ships);
+            routes.insert(shape, route);
+        }
+        self.routes = routes;
+        true
+    }
+
+    /// Removes the link through the given waypoint of every route, removing
+    /// routes left without links. Routes split in two continue on a line
+    /// not in use, and links in the middle are kept if no line is left.
+    /// Returns the ships of removed routes.
+    pub fn remove_links_at(&mut self, waypoint: Waypoint) -> Vec<Ship> {
+        let mut ships = vec![];
+        let mut split_off = vec![];
+        let free_lines = self
+            .lines
+            .iter()
+            .filter(|shape| !self.routes.contains_key(shape))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.routes.retain(|_, route| {
+            if !route.contains(waypoint) {
+                return true;
+            }
+            match route.remove_link(waypoint, split_off.len() < free_lines.len()) {
+                LinkRemoval::Kept => {
+                    ships.extend(route.remove_ships());
+                    false
+                }
+                LinkRemoval::Refused | LinkRemoval::Removed => true,
+                LinkRemoval::Split(rest) => {
+                    split_off.push(rest);
+                    true
+                }
+            }
+        });
+        self.routes.extend(free_lines.into_iter().zip(split_off));
+        ships
+    }
+
     /// Removes all routes going through the given waypoint.
     pub fn remove_routes_at(&mut self, waypoint: Waypoint) -> Vec<Ship> {
         let mut ships = vec![];
@@ -87,6 +303,44 @@ impl World {
         ships
     }
 
+    /// Returns the colors currently being traded.
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Adds the color to the colors being traded, returning false if it
+    /// already is.
+    pub fn add_color(&mut self, color: Color) -> bool {
+        if self.colors.contains(&color) {
+            false
+        } else {
+            self.colors.push(color);
+            true
+        }
+    }
+
+    /// Returns the route lines which can be drawn.
+    pub fn lines(&self) -> &[RouteShape] {
+        &self.lines
+    }
+
+    /// Makes the given number of further route lines available, returning
+    /// the number of lines added as fewer may be left.
+    pub fn add_lines(&mut self, lines: usize) -> usize {
+        let added = RouteShape::values()
+            .into_iter()
+            .filter(|shape| !self.lines.contains(shape))
+            .take(lines)
+            .collect::<Vec<_>>();
+        self.lines.extend(&added);
+        added.len()
+    }
+
+    /// Returns a reference to the shipyard.
+    pub fn shipyard(&self) -> &Shipyard {
+        &self.shipyard
+    }
+
     /// Returns a mutable reference to the shipyard.
     pub fn shipyard_mut(&mut self) -> &mut Shipyard {
         &mut self.shipyard
@@ -95,6 +349,7 @@ impl World {
     /// Return all the current valid start points for drawing/extending a route.
     pub fn allowed_starts(&self, shape: RouteShape) -> Vec<Position> {
         match self.routes.get(&shape) {
+            Some(route) if route.is_loop() => vec![],
             Some(route) => vec![
                 route
                     .ports()
@@ -111,13 +366,18 @@ impl World {
     }
 
     /// Return all the current valid end points for drawing/extending a route.
+    /// The first port of a route with at least two links closes it into a loop.
     pub fn allowed_ends(&self, start: Position, shape: RouteShape) -> Vec<Position> {
         match self.routes.get(&shape) {
+            Some(route) if route.is_loop() => vec![],
             Some(route) => self
                 .open_ports
                 .iter()
                 .filter_map(|port| {
-                    if !route.ports().any(|p| *p == port.position()) {
+                    let closes_loop = route.num_links() >= 2
+                        && route.ports().next() == Some(&port.position())
+                        && route.ports().last() == Some(&start);
+                    if closes_loop || !route.ports().any(|p| *p == port.position()) {
                         Some(port.position())
                     } else {
                         None
@@ -136,6 +396,29 @@ impl World {
         }
     }
 
+    /// Return all the ports which can be inserted into the middle of a route.
+    pub fn allowed_inserts(&self, shape: RouteShape) -> Vec<Position> {
+        match self.routes.get(&shape) {
+            Some(route) => self
+                .open_ports
+                .iter()
+                .map(|port| port.position())
+                .filter(|position| !route.ports().any(|p| p == position))
+                .collect::<Vec<_>>(),
+            None => vec![],
+        }
+    }
+
+    /// Inserts the given port into the link of the route through the given
+    /// waypoint. Returns true if the port was inserted.
+    pub fn insert_port(&mut self, shape: RouteShape, waypoint: Waypoint, port: Position) -> bool {
+        let occupied = self.occupied_waypoints();
+        match self.routes.get_mut(&shape) {
+            Some(route) => route.insert_port(&self.navigation, &occupied, waypoint, port),
+            None => false,
+        }
+    }
+
     /// Creates a new route (if non exists) and adds a new link between start and goal.
     pub fn add_route(
         &mut self,
@@ -144,42 +427,50 @@ impl World {
         goal: Position,
         path: Vec<Waypoint>,
     ) {
-        let waypoints = self
-            .routes
-            .values()
-            .flat_map(|r| r.waypoints().into_iter().cloned())
-            .collect::<Vec<_>>();
+        let occupied = self.occupied_waypoints();
         let route = self.routes.entry(color).or_insert_with(Route::new);
-        route.add_link(&self.map, &self.open_ports, &waypoints, start, goal, path);
+        route.add_link(&self.navigation, &occupied, start, goal, path);
     }
 
     /// Returns the tile at the given position.
     pub fn tile(&self, position: Position) -> Option<&Tile> {
-        self.map.iter().find(|tile| tile.position() == position)
+        if position.x < 0
+            || position.y < 0
+            || position.x as u32 >= self.width
+            || position.y as u32 >= self.height
+        {
+            None
+        } else {
+            self.map
+                .get((position.y as u32 * self.width + position.x as u32) as usize)
+        }
     }
 
     /// Returns the port at the given position.
     pub fn port(&self, position: Position) -> Option<&Port> {
-        self.open_ports
-            .iter()
-            .find(|port| port.position() == position)
+        self.port_index
+            .get(&position)
+            .map(|index| &self.open_ports[*index])
     }
 
     /// Returns all reachable tiles from a given position which a trade
     /// route can pass through.
     pub fn reachable(&self, position: Position) -> Vec<Position> {
-        reachable(&self.map, &self.open_ports, position)
+        self.navigation.reachable(position)
     }
 
     /// Finds the shortest path from start to goal using astar with
     /// manhattan distance heuristic.
     pub fn route(&self, start: Position, goal: Position) -> Option<(i32, Vec<Position>)> {
-        let waypoints = self
-            .routes
+        find_path(&self.navigation, &self.occupied_waypoints(), start, goal)
+    }
+
+    /// Returns the set of all waypoints used by some route.
+    fn occupied_waypoints(&self) -> HashSet<Waypoint> {
+        self.routes
             .values()
             .flat_map(|r| r.waypoints().into_iter().cloned())
-            .collect::<Vec<_>>();
-        find_path(&self.map, &self.open_ports, &waypoints, start, goal)
+            .collect()
     }
 }
 
@@ -188,10 +479,18 @@ impl Default for World {
     fn default() -> Self {
         World {
             map: vec![],
+            width: 0,
+            height: 0,
+            navigation: NavigationGrid::default(),
             open_ports: vec![],
+            port_index: HashMap::new(),
             closed_ports: vec![],
+            port_names: HashMap::new(),
+            port_schedule: vec![],
             routes: HashMap::new(),
-            shipyard: Shipyard::new(),
+            shipyard: Shipyard::new(DifficultySettings::default()),
+            colors: vec![],
+            lines: default_lines(),
         }
     }
 }